@@ -0,0 +1,204 @@
+//! Measures message throughput through the Postmaster's routing table while it is under contention.
+//! Several producers each send a stream of messages to their own consumer. In the contended runs, another producer is
+//! meanwhile stuck sending to a mailbox which is never emptied, so all of its sends are waiting for space until they time out.
+//! With a routing table which locks on every send, that one stuck destination holds up every other destination, and
+//! `try_send()` fails for destinations whose queues have plenty of space.
+//! The same workload is run against a baseline routing table behind a single Mutex, which is how the Postmaster's routing
+//! table used to work, for comparison.
+//!
+//! Run with `cargo bench --bench routing`.
+
+use std::time::{Duration, Instant};
+
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::{Sender, channel};
+
+const PRODUCERS: usize = 4;
+const MESSAGES_PER_PRODUCER: usize = 50_000;
+const QUEUE_SIZE: usize = 64;
+const TIMEOUT: Duration = Duration::from_micros(10_000);
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Addresses {
+    Producer,
+    Stuck,
+    Consumer0,
+    Consumer1,
+    Consumer2,
+    Consumer3,
+}
+
+const CONSUMERS: [Addresses; PRODUCERS] = [
+    Addresses::Consumer0,
+    Addresses::Consumer1,
+    Addresses::Consumer2,
+    Addresses::Consumer3,
+];
+
+#[derive(Debug)]
+enum Payloads {
+    Tick,
+}
+
+init_postmaster!(Addresses, Payloads, 10_000);
+
+/// A routing table which holds a single lock for the whole of every send, as the Postmaster's routing table used to.
+static BASELINE: Mutex<[Option<Sender<Payloads>>; Addresses::COUNT]> =
+    Mutex::const_new([const { None }; Addresses::COUNT]);
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        for consumer in CONSUMERS {
+            let (sender, mut receiver) = channel(QUEUE_SIZE);
+            postmaster::register(consumer, sender).await.unwrap();
+            tokio::spawn(async move { while receiver.recv().await.is_some() {} });
+        }
+
+        report("send, uncontended", send_throughput().await);
+        report("try_send, uncontended", try_send_throughput().await);
+
+        // The stuck mailbox fills up after its first message, and is never read
+        let (sender, _receiver) = channel(1);
+        postmaster::register(Addresses::Stuck, sender).await.unwrap();
+        let stuck = tokio::spawn(async {
+            loop {
+                let _ = postmaster::send(Addresses::Stuck, Addresses::Producer, Payloads::Tick).await;
+            }
+        });
+
+        report("send, one stuck destination", send_throughput().await);
+        report("try_send, one stuck destination", try_send_throughput().await);
+        stuck.abort();
+
+        for consumer in CONSUMERS {
+            let (sender, mut receiver) = channel(QUEUE_SIZE);
+            BASELINE.lock().await[consumer as usize] = Some(sender);
+            tokio::spawn(async move { while receiver.recv().await.is_some() {} });
+        }
+
+        report("Mutex send, uncontended", baseline_send_throughput().await);
+        report("Mutex try_send, uncontended", baseline_try_send_throughput().await);
+
+        let (sender, _receiver) = channel(1);
+        BASELINE.lock().await[Addresses::Stuck as usize] = Some(sender);
+        let stuck = tokio::spawn(async {
+            loop {
+                let _ = baseline_send(Addresses::Stuck).await;
+            }
+        });
+
+        report("Mutex send, one stuck destination", baseline_send_throughput().await);
+        report("Mutex try_send, one stuck destination", baseline_try_send_throughput().await);
+        stuck.abort();
+    });
+}
+
+/// Returns the number of messages sent, the number of failed sends, and the time taken.
+async fn send_throughput() -> (usize, usize, Duration) {
+    run_producers(|destination| async move {
+        postmaster::send(destination, Addresses::Producer, Payloads::Tick)
+            .await
+            .is_ok()
+    })
+    .await
+}
+
+/// Returns the number of messages sent, the number of failed sends, and the time taken.
+/// Full queues are retried, so only failures for other reasons are counted.
+async fn try_send_throughput() -> (usize, usize, Duration) {
+    run_producers(|destination| async move {
+        loop {
+            match postmaster::try_send(destination, Addresses::Producer, Payloads::Tick) {
+                Ok(()) => return true,
+                Err(PostmasterError::TrySendFailed) => tokio::task::yield_now().await,
+                Err(_) => return false,
+            }
+        }
+    })
+    .await
+}
+
+async fn baseline_send(destination: Addresses) -> Result<(), PostmasterError> {
+    tokio::time::timeout(TIMEOUT, async {
+        match &BASELINE.lock().await[destination as usize] {
+            None => Err(PostmasterError::NoRecipient),
+            Some(sender) => sender
+                .send(Payloads::Tick)
+                .await
+                .map_err(|_| PostmasterError::ReceiverClosed),
+        }
+    })
+    .await
+    .unwrap_or(Err(PostmasterError::Timeout))
+}
+
+/// Fails with `TryLockFailed` whenever another send holds the lock, as the Postmaster's `try_send()` used to.
+#[allow(deprecated)]
+fn baseline_try_send(destination: Addresses) -> Result<(), PostmasterError> {
+    match &BASELINE.try_lock().map_err(|_| PostmasterError::TryLockFailed)?[destination as usize] {
+        None => Err(PostmasterError::NoRecipient),
+        Some(sender) => sender
+            .try_send(Payloads::Tick)
+            .map_err(|_| PostmasterError::TrySendFailed),
+    }
+}
+
+/// Returns the number of messages sent, the number of failed sends, and the time taken.
+async fn baseline_send_throughput() -> (usize, usize, Duration) {
+    run_producers(|destination| async move { baseline_send(destination).await.is_ok() }).await
+}
+
+/// Returns the number of messages sent, the number of failed sends, and the time taken.
+/// As with the Postmaster, full queues are retried, so only failures for other reasons (including a contended lock) are counted.
+async fn baseline_try_send_throughput() -> (usize, usize, Duration) {
+    run_producers(|destination| async move {
+        loop {
+            match baseline_try_send(destination) {
+                Ok(()) => return true,
+                Err(PostmasterError::TrySendFailed) => tokio::task::yield_now().await,
+                Err(_) => return false,
+            }
+        }
+    })
+    .await
+}
+
+async fn run_producers<F, Fut>(send: F) -> (usize, usize, Duration)
+where
+    F: Fn(Addresses) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = bool> + Send,
+{
+    let start = Instant::now();
+    let producers: Vec<_> = CONSUMERS
+        .into_iter()
+        .map(|destination| {
+            let send = send.clone();
+            tokio::spawn(async move {
+                let mut failures = 0;
+                for _ in 0..MESSAGES_PER_PRODUCER {
+                    if !send(destination).await {
+                        failures += 1;
+                    }
+                }
+                failures
+            })
+        })
+        .collect();
+    let mut failures = 0;
+    for producer in producers {
+        failures += producer.await.unwrap();
+    }
+    (PRODUCERS * MESSAGES_PER_PRODUCER, failures, start.elapsed())
+}
+
+fn report(name: &str, (messages, failures, elapsed): (usize, usize, Duration)) {
+    println!(
+        "{name:<40} {:>12.0} messages/s, {failures} of {messages} sends failed",
+        (messages - failures) as f64 / elapsed.as_secs_f64()
+    );
+}
@@ -5,7 +5,6 @@
     reason = "mem::forget is generally not safe to do with esp_hal types, especially those \
     holding buffers for the duration of a data transfer."
 )]
-#![feature(variant_count)]
 
 use embassy_executor::Spawner;
 use embassy_time::{Duration, Timer};
@@ -1,8 +1,7 @@
 #![no_std]
-#![feature(variant_count)]
 
 use embassy_executor::Spawner;
-use post_haste::init_postmaster;
+use post_haste::{PostHasteAddress, init_postmaster};
 
 use crate::polite_agent::PoliteAgent;
 
@@ -12,7 +11,7 @@ enum Payloads {
     Hello,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PostHasteAddress)]
 enum Address {
     PoliteAgentA,
     PoliteAgentB,
@@ -23,7 +22,8 @@ init_postmaster!(Address, Payloads);
 pub async fn run(spawner: Spawner) {
     postmaster::register_agent!(spawner, PoliteAgentA, PoliteAgent, ()).unwrap();
 
-    postmaster::register_agent!(spawner, PoliteAgentB, PoliteAgent, ()).unwrap();
+    postmaster::register_agent!(spawner, PoliteAgentB, PoliteAgent, (), priority_levels: 2, capacity: 2)
+        .unwrap();
 
     postmaster::send(
         Address::PoliteAgentA,
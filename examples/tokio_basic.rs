@@ -1,18 +1,18 @@
 //! This example provides a very simple scenario of two Agents exchanging messages with each other.
-#![feature(variant_count)]
 
 use core::time::Duration;
 
-use post_haste::init_postmaster;
+use post_haste::{PostHasteAddress, init_postmaster};
 use tokio::time::sleep;
 
 use crate::polite_agent::PoliteAgent;
 
+#[derive(Debug)]
 enum Payloads {
     Hello,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
 enum Addresses {
     A,
     B,
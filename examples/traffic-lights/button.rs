@@ -4,34 +4,37 @@
 use tokio::io::{self, AsyncBufReadExt, BufReader};
 
 use crate::{
-    Addresses, Payloads, display::DisplayMessage, postmaster, sequencer::SequencerMessage,
+    Addresses,
+    display::{DisplayAgent, DisplayMessage},
+    postmaster::AgentRef,
+    sequencer::{SequencerAgent, SequencerMessage},
 };
 
 /// Asynchronous task for checking the crossing "button"
-pub async fn button_task() -> ! {
+pub async fn button_task(
+    sequencer: AgentRef<SequencerAgent>,
+    display: AgentRef<DisplayAgent>,
+) -> ! {
     let mut reader = BufReader::new(io::stdin()).lines();
     loop {
         // Infinitely asynchronously await for a line of input text from the terminal
         if reader.next_line().await.unwrap().is_some() {
             // Upon receiving a line of text, send a message to the Sequencer Agent
-            postmaster::send(
-                Addresses::SequencerAgent,
-                Addresses::ButtonTask,
-                Payloads::Sequencer(SequencerMessage::ButtonPress),
-            )
-            .await
-            .unwrap();
+            sequencer
+                .send(Addresses::ButtonTask, SequencerMessage::ButtonPress)
+                .await
+                .unwrap();
 
             // Also send a debug message - this is just to help understand how this example works
-            postmaster::send(
-                Addresses::DisplayAgent,
-                Addresses::ButtonTask,
-                Payloads::Display(DisplayMessage::DebugMessage(String::from(
-                    "Message sent from ButtonTask to LightsAgent",
-                ))),
-            )
-            .await
-            .unwrap();
+            display
+                .send(
+                    Addresses::ButtonTask,
+                    DisplayMessage::DebugMessage(String::from(
+                        "Message sent from ButtonTask to LightsAgent",
+                    )),
+                )
+                .await
+                .unwrap();
         }
     }
 }
@@ -6,12 +6,12 @@ use std::io::{self, Stdout};
 // Library for getting the current time - this is for the debug messages
 use chrono::Local;
 
-use crate::{Addresses, Payloads, postmaster, sequencer};
+use crate::{Addresses, postmaster, sequencer};
 use hardware::{ButtonLight, PedestrianLights, TrafficLights};
-use post_haste::agent::{Agent, Inbox};
+use post_haste::handler::{Context, MessageHandler};
 
 /// Declares valid messages which can be sent to this agent
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum DisplayMessage {
     /// Update the display with the current sequencer state
     SetSequenceState {
@@ -184,10 +184,14 @@ pub(crate) struct DisplayAgent {
     debug_messages: Vec<String>,
 }
 
-impl Agent for DisplayAgent {
+/// Rather than implementing Agent and writing its own main loop, the display agent
+/// implements MessageHandler, which calls handle() for each message it receives
+impl MessageHandler for DisplayAgent {
     type Address = Addresses;
-    type Message = postmaster::Message;
     type Config = ();
+    type Message = postmaster::Message<DisplayMessage>;
+    // Only display messages are received, as that is the message type declared here
+    type Received = postmaster::Message<DisplayMessage>;
 
     async fn create(_address: Self::Address, _config: Self::Config) -> Self {
         Self {
@@ -199,19 +203,8 @@ impl Agent for DisplayAgent {
         }
     }
 
-    async fn run(mut self, mut inbox: Inbox<Self::Message>) -> ! {
-        loop {
-            if let Some(message) = inbox.recv().await {
-                // Await messages
-                match message.payload {
-                    Payloads::Display(lights_message) => self.message_handler(lights_message),
-                    _ => println!(
-                        "DisplayAgent received unsupported message {:?}",
-                        message.payload
-                    ),
-                }
-            }
-        }
+    async fn handle(&mut self, _ctx: &mut Context<Self::Address>, message: Self::Message) {
+        self.message_handler(message.payload)
     }
 }
 
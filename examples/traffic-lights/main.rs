@@ -1,7 +1,4 @@
-// variant_count is required for init_postmaster
-#![feature(variant_count)]
-
-use post_haste::init_postmaster;
+use post_haste::{PostHasteAddress, PostHastePayload, PostHasteTopic, init_postmaster};
 use std::process::exit;
 
 use crate::{
@@ -17,8 +14,11 @@ mod sequencer;
 
 /// Declare the payloads for project. These are the messages that each agent can
 /// send. The contents of each message is usually an enum which is defined in
-/// the file implementing that agent
-#[derive(Debug)]
+/// the file implementing that agent. Payloads must implement Clone in order to be
+/// published to several subscribers.
+/// Deriving PostHastePayload lets each agent's message enum be sent directly, and
+/// lets each agent receive only its own enum
+#[derive(Debug, Clone, PostHastePayload)]
 pub(crate) enum Payloads {
     // Messages to be sent to the display agent
     Display(DisplayMessage),
@@ -29,7 +29,7 @@ pub(crate) enum Payloads {
 /// Declare the addresses for post-haste. Messages can be sent from any address
 /// but Main and ButtonTask do not have agent implementations, so cannot receive
 /// messages
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
 pub(crate) enum Addresses {
     Main,
     DisplayAgent,
@@ -37,37 +37,45 @@ pub(crate) enum Addresses {
     ButtonTask,
 }
 
-init_postmaster!(Addresses, Payloads);
+/// Declare the topics which agents can subscribe to. Messages published on a topic
+/// are delivered to every subscriber, without the publisher needing to know who
+/// they are
+#[derive(Debug, Clone, Copy, PostHasteTopic)]
+pub(crate) enum Topics {
+    // Changes to the state of the sequencer
+    SequenceState,
+}
+
+init_postmaster!(Addresses, Payloads, topics: Topics);
 
 #[tokio::main]
 async fn main() {
-    // Register each agent with its address and the struct implementing the agent
-    // These agents do not require config, so the unit type is passed
-    postmaster::register_agent!(DisplayAgent, DisplayAgent, ()).unwrap();
-    postmaster::register_agent!(SequencerAgent, SequencerAgent, ()).unwrap();
+    // Register each agent with its address and the struct implementing the agent.
+    // Registering returns a typed handle, whose send function only accepts the
+    // agent's own message type. The display agent does not require config, so the
+    // unit type is passed, while the sequencer is given the display's handle
+    let display = postmaster::register_agent!(DisplayAgent, DisplayAgent, ()).unwrap();
+    let sequencer = postmaster::register_agent!(SequencerAgent, SequencerAgent, display).unwrap();
+    // The display agent wants to know whenever the sequencer changes state
+    postmaster::subscribe(Addresses::DisplayAgent, Topics::SequenceState);
     // Spawn the button task using tokio
-    tokio::spawn(button_task());
+    tokio::spawn(button_task(sequencer, display));
 
     // Send a message to the Display Agent with a payload requesting for a message
     // to be displayed in the terminal.
-    postmaster::send(
-        Addresses::DisplayAgent,
-        Addresses::Main,
-        Payloads::Display(DisplayMessage::DebugMessage(
-            "Press enter to press the crossing button".to_string(),
-        )),
-    )
-    .await
-    .unwrap();
+    display
+        .send(
+            Addresses::Main,
+            DisplayMessage::DebugMessage("Press enter to press the crossing button".to_string()),
+        )
+        .await
+        .unwrap();
 
     // Send a message to the Sequencer Agent requesting for the sequencing to begin
-    postmaster::send(
-        Addresses::SequencerAgent,
-        Addresses::Main,
-        Payloads::Sequencer(SequencerMessage::Begin),
-    )
-    .await
-    .unwrap();
+    sequencer
+        .send(Addresses::Main, SequencerMessage::Begin)
+        .await
+        .unwrap();
 
     // If CTRL+C is pressed then the program will exit nicely
     let _ = tokio::signal::ctrl_c().await;
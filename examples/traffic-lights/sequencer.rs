@@ -4,11 +4,11 @@
 
 use post_haste::agent::Agent;
 
-use crate::display::DisplayMessage;
-use crate::{Addresses, Payloads, consts, postmaster};
+use crate::display::{DisplayAgent, DisplayMessage};
+use crate::{Topics, consts, postmaster};
 
 /// An enumeration listing the potential messages that can be sent to the Sequencer agent
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum SequencerMessage {
     /// Signal for the sequencer to begin
     Begin,
@@ -50,18 +50,23 @@ pub(crate) enum SequencerState {
 
 /// The struct for the sequencer agent
 pub(crate) struct SequencerAgent {
-    address: crate::Addresses,
+    /// Sends messages with the sequencer's own address as their source, and
+    /// keeps the timer which moves the sequencer onto its next state
+    ctx: postmaster::AgentContext,
+    /// A typed handle for the display agent, which only accepts display messages
+    display: postmaster::AgentRef<DisplayAgent>,
     state: SequencerState,
 }
 
 impl Agent for SequencerAgent {
     type Address = crate::Addresses;
-    type Message = postmaster::Message;
-    type Config = ();
+    type Message = postmaster::Message<SequencerMessage>;
+    type Config = postmaster::AgentRef<DisplayAgent>;
 
-    async fn create(address: Self::Address, _config: Self::Config) -> Self {
+    async fn create(address: Self::Address, display: Self::Config) -> Self {
         Self {
-            address,
+            ctx: postmaster::AgentContext::new(address),
+            display,
             state: SequencerState::RedCrossEnding,
         }
     }
@@ -70,16 +75,10 @@ impl Agent for SequencerAgent {
         loop {
             // The agent asynchronously awaits for a message to be sent to it in
             // an infinite loop
+            // Only sequencer messages are delivered here, as that is the message
+            // type declared above
             let received_message = inbox.recv().await.unwrap();
-            match received_message.payload {
-                // If a sequencer message is received then it will be handled
-                Payloads::Sequencer(message) => self.handle_message(message).await,
-                // Any other messages are not valid - this would suggest a mistake in the code
-                _ => println!(
-                    "SequencerAgent received unsupported message {:?}",
-                    received_message.payload
-                ),
-            }
+            self.handle_message(received_message.payload).await;
         }
     }
 }
@@ -93,23 +92,25 @@ impl SequencerAgent {
         }
     }
 
-    async fn send_current_state_to_lights_agent(&mut self) {
-        postmaster::send(
-            Addresses::DisplayAgent,
-            self.address,
-            Payloads::Display(DisplayMessage::SetSequenceState {
+    /// Publish the current state, so that any agent which has subscribed to
+    /// sequencer state changes (here, just the display agent) is updated
+    async fn publish_current_state(&mut self) {
+        let report = postmaster::publish(
+            Topics::SequenceState,
+            self.ctx.address(),
+            DisplayMessage::SetSequenceState {
                 sequence_state: self.state.clone(),
-            }),
+            },
         )
-        .await
-        .unwrap();
+        .await;
+        assert!(report.is_success());
     }
 
     /// The sole purpose of internal messages in this agent is to signal that the
     /// sequencer should move onto the next state in the sequence
     async fn handle_internal_message(&mut self) {
         self.set_next_state().await;
-        self.send_current_state_to_lights_agent().await;
+        self.publish_current_state().await;
     }
 
     async fn handle_button_press(&mut self) {
@@ -118,7 +119,7 @@ impl SequencerAgent {
             // state, update the display, and schedule the next internal message
             SequencerState::Green => {
                 self.state = SequencerState::GreenCrossPending;
-                self.send_current_state_to_lights_agent().await;
+                self.publish_current_state().await;
                 self.schedule_next_state().await;
             }
             // In the following states, nothing should happen when the button is pressed
@@ -130,20 +131,20 @@ impl SequencerAgent {
             | SequencerState::RedToGreenCrossPending => {
                 // Do nothing!
                 // Send a debug message - this is just to help understand how this example works
-                postmaster::send(
-                    Addresses::DisplayAgent,
-                    Addresses::SequencerAgent,
-                    Payloads::Display(DisplayMessage::DebugMessage(String::from(
-                        "Sequencer ignored the button press",
-                    ))),
-                )
-                .await
-                .unwrap()
+                self.display
+                    .send(
+                        self.ctx.address(),
+                        DisplayMessage::DebugMessage(String::from(
+                            "Sequencer ignored the button press",
+                        )),
+                    )
+                    .await
+                    .unwrap()
             }
             // Special case if the pedestrian presses the button in the Red to Green state
             SequencerState::RedToGreen => {
                 self.state = SequencerState::RedToGreenCrossPending;
-                self.send_current_state_to_lights_agent().await;
+                self.publish_current_state().await;
                 // Do not schedule next state - in RedToGreen state, a delayed
                 // message will already have been sent!
             }
@@ -152,19 +153,19 @@ impl SequencerAgent {
 
     /// Function to begin the sequencer
     async fn begin(&mut self) {
-        self.send_current_state_to_lights_agent().await;
+        self.publish_current_state().await;
         self.schedule_next_state().await;
 
         // Also send a debug message - this is just to help understand how this example works
-        postmaster::send(
-            Addresses::DisplayAgent,
-            Addresses::SequencerAgent,
-            Payloads::Display(DisplayMessage::DebugMessage(String::from(
-                "Sequencer received message to begin sequencing",
-            ))),
-        )
-        .await
-        .unwrap()
+        self.display
+            .send(
+                self.ctx.address(),
+                DisplayMessage::DebugMessage(String::from(
+                    "Sequencer received message to begin sequencing",
+                )),
+            )
+            .await
+            .unwrap()
     }
 
     /// For each state, set the new state and in some instances send a delayed
@@ -201,30 +202,25 @@ impl SequencerAgent {
             }
         }
         // In all cases the display agent should be updated
-        self.send_current_state_to_lights_agent().await;
+        self.publish_current_state().await;
 
         // Also send a debug message - this is just to help understand how this example works
-        postmaster::send(
-            Addresses::DisplayAgent,
-            Addresses::SequencerAgent,
-            Payloads::Display(DisplayMessage::DebugMessage(format!(
-                "Sequencer updated its state to {:?}",
-                self.state
-            ))),
-        )
-        .await
-        .unwrap()
+        self.display
+            .send(
+                self.ctx.address(),
+                DisplayMessage::DebugMessage(format!(
+                    "Sequencer updated its state to {:?}",
+                    self.state
+                )),
+            )
+            .await
+            .unwrap()
     }
 
     /// Helper function to send delayed internal messages (from Sequencer Agent
     /// to Sequencer Agent). The delay depends on the current state
     async fn schedule_next_state(&mut self) {
-        postmaster::message(
-            self.address,
-            self.address,
-            Payloads::Sequencer(SequencerMessage::InternalMessage),
-        )
-        .with_delay(match self.state {
+        let delay = match self.state {
             SequencerState::Green => unreachable!(),
             SequencerState::GreenCrossPending => consts::GREEN_TO_AMBER_DELAY,
             SequencerState::GreenToRed => consts::AMBER_TO_RED_DELAY,
@@ -234,20 +230,22 @@ impl SequencerAgent {
             SequencerState::RedToGreen | SequencerState::RedToGreenCrossPending => {
                 consts::AMBER_TO_GREEN_DELAY
             }
-        })
-        .send()
-        .await
-        .unwrap();
+        };
+        // Starting the timer again replaces any which is still pending
+        self.ctx
+            .start_timer("next-state", delay, SequencerMessage::InternalMessage)
+            .await
+            .unwrap();
 
         // Also send a debug message - this is just to help understand how this example works
-        postmaster::send(
-            Addresses::DisplayAgent,
-            Addresses::SequencerAgent,
-            Payloads::Display(DisplayMessage::DebugMessage(String::from(
-                "Sequencer agent sent a delayed internal message to itself",
-            ))),
-        )
-        .await
-        .unwrap();
+        self.display
+            .send(
+                self.ctx.address(),
+                DisplayMessage::DebugMessage(String::from(
+                    "Sequencer agent sent a delayed internal message to itself",
+                )),
+            )
+            .await
+            .unwrap();
     }
 }
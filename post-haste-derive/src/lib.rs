@@ -0,0 +1,206 @@
+//! Derive macros for post-haste.
+//! These are re-exported by post-haste, so there should be no need to depend on this crate directly.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{Data, DataEnum, DeriveInput, Error, Fields, Ident, Type, Variant, parse_macro_input};
+
+/// Implements `post_haste::PostHasteAddress` for an enum, so that it can be used as the address enum in `init_postmaster!()`.
+/// Every variant must be a unit variant.
+#[proc_macro_derive(PostHasteAddress)]
+pub fn derive_address(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let variants = match unit_variants(&input, "PostHasteAddress") {
+        Ok(variants) => variants,
+        Err(error) => return error.into_compile_error().into(),
+    };
+    let ident = &input.ident;
+    let count = variants.len();
+    let indices = 0..count;
+    let names = variants.iter().map(|variant| variant.to_string());
+    quote! {
+        impl ::post_haste::PostHasteAddress for #ident {
+            const COUNT: usize = #count;
+
+            #[inline]
+            fn index(self) -> usize {
+                match self {
+                    #(Self::#variants => #indices,)*
+                }
+            }
+
+            fn from_index(index: usize) -> Option<Self> {
+                const VARIANTS: &[#ident] = &[#(#ident::#variants),*];
+                VARIANTS.get(index).copied()
+            }
+
+            fn name(self) -> &'static str {
+                match self {
+                    #(Self::#variants => #names,)*
+                }
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements `post_haste::PostHasteTopic` for an enum, so that it can be used as the topic enum in `init_postmaster!()`.
+/// Every variant must be a unit variant.
+#[proc_macro_derive(PostHasteTopic)]
+pub fn derive_topic(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let variants = match unit_variants(&input, "PostHasteTopic") {
+        Ok(variants) => variants,
+        Err(error) => return error.into_compile_error().into(),
+    };
+    let ident = &input.ident;
+    let count = variants.len();
+    let indices = 0..count;
+    quote! {
+        impl ::post_haste::PostHasteTopic for #ident {
+            const COUNT: usize = #count;
+
+            #[inline]
+            fn index(self) -> usize {
+                match self {
+                    #(Self::#variants => #indices,)*
+                }
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements conversions between the payload enum passed to `init_postmaster!()` and the message types of individual Agents.
+/// Each variant with a single unnamed field is taken to carry the message type of an Agent (or of several).
+/// For each of these, `From<MessageType>` and `post_haste::PayloadVariant` are implemented, so that the message type can be sent anywhere a payload is expected, and an Agent can declare `postmaster::Message<MessageType>` as its Message.
+/// Other variants are left alone, as are variants marked `#[post_haste(skip)]` (e.g. one carrying a plain `u8`, which should not be sendable as a bare `u8`).
+/// Each message type may only be carried by one of the remaining variants.
+#[proc_macro_derive(PostHastePayload, attributes(post_haste))]
+pub fn derive_payload(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let variants = match wrapping_variants(&input) {
+        Ok(variants) => variants,
+        Err(error) => return error.into_compile_error().into(),
+    };
+    let ident = &input.ident;
+    let conversions = variants.iter().map(|(variant, inner)| {
+        quote! {
+            impl ::core::convert::From<#inner> for #ident {
+                fn from(message: #inner) -> Self {
+                    Self::#variant(message)
+                }
+            }
+
+            impl ::post_haste::PayloadVariant<#ident> for #inner {
+                fn matches(payload: &#ident) -> bool {
+                    matches!(payload, #ident::#variant(_))
+                }
+
+                fn narrow(payload: #ident) -> ::core::result::Result<Self, #ident> {
+                    match payload {
+                        #ident::#variant(message) => Ok(message),
+                        #[allow(unreachable_patterns)]
+                        other => Err(other),
+                    }
+                }
+            }
+        }
+    });
+    quote! { #(#conversions)* }.into()
+}
+
+/// Collects the variants of the enum which carry a single unnamed field and are not skipped, along with the type of that field.
+/// Fails if two of them carry the same type, as the conversions for that type would conflict.
+fn wrapping_variants(input: &DeriveInput) -> Result<Vec<(&Ident, &Type)>, Error> {
+    let data = enum_data(input, "PostHastePayload")?;
+    let mut variants: Vec<(&Ident, &Type)> = Vec::new();
+    let mut errors: Option<Error> = None;
+    for variant in &data.variants {
+        if is_skipped(variant)? {
+            continue;
+        }
+        let Fields::Unnamed(fields) = &variant.fields else {
+            continue;
+        };
+        if fields.unnamed.len() != 1 {
+            continue;
+        }
+        let inner = &fields.unnamed[0].ty;
+        let inner_name = quote!(#inner).to_string();
+        if let Some((earlier, _)) = variants
+            .iter()
+            .find(|(_, earlier)| quote!(#earlier).to_string() == inner_name)
+        {
+            let error = Error::new_spanned(
+                inner,
+                format!(
+                    "`{inner_name}` is carried by both `{earlier}` and `{}`, so PostHastePayload cannot tell which to convert it into; mark all but one of them `#[post_haste(skip)]`",
+                    variant.ident
+                ),
+            );
+            match &mut errors {
+                Some(errors) => errors.combine(error),
+                None => errors = Some(error),
+            }
+            continue;
+        }
+        variants.push((&variant.ident, inner));
+    }
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(variants),
+    }
+}
+
+/// Checks whether a variant is marked `#[post_haste(skip)]`.
+fn is_skipped(variant: &Variant) -> Result<bool, Error> {
+    let mut skipped = false;
+    for attr in variant
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("post_haste"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skipped = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported post_haste attribute, expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skipped)
+}
+
+/// Collects the names of the enum's variants, checking that they are all unit variants.
+fn unit_variants<'a>(input: &'a DeriveInput, derive: &str) -> Result<Vec<&'a Ident>, Error> {
+    let data = enum_data(input, derive)?;
+    data.variants
+        .iter()
+        .map(|variant| match variant.fields {
+            Fields::Unit => Ok(&variant.ident),
+            _ => Err(Error::new_spanned(
+                variant,
+                format!("{derive} requires every variant to be a unit variant"),
+            )),
+        })
+        .collect()
+}
+
+/// Checks that the input is a non-generic enum.
+fn enum_data<'a>(input: &'a DeriveInput, derive: &str) -> Result<&'a DataEnum, Error> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            format!("{derive} can only be derived for enums"),
+        ));
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            format!("{derive} cannot be derived for generic enums"),
+        ));
+    }
+    Ok(data)
+}
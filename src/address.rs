@@ -0,0 +1,27 @@
+//! Traits which tell the Postmaster about the address and topic enums passed to `init_postmaster!()`.
+//! Both are normally derived, with `#[derive(PostHasteAddress)]` and `#[derive(PostHasteTopic)]` respectively.
+
+/// Implemented by the address enum, giving the number of addresses and a stable index for each.
+/// The Postmaster's tables are sized by `COUNT` and looked up by `index()`, rather than by casting the enum, so that the variants' discriminants don't matter.
+pub trait PostHasteAddress: Copy + 'static {
+    /// The number of addresses (variants of the enum).
+    const COUNT: usize;
+
+    /// The position of the address among the variants of the enum, from 0 up to `COUNT - 1`.
+    fn index(self) -> usize;
+
+    /// The address at a position among the variants of the enum, if there is one.
+    fn from_index(index: usize) -> Option<Self>;
+
+    /// The name of the address's variant.
+    fn name(self) -> &'static str;
+}
+
+/// Implemented by the topic enum, giving the number of topics and a stable index for each.
+pub trait PostHasteTopic: Copy + 'static {
+    /// The number of topics (variants of the enum).
+    const COUNT: usize;
+
+    /// The position of the topic among the variants of the enum, from 0 up to `COUNT - 1`.
+    fn index(self) -> usize;
+}
@@ -1,12 +1,458 @@
+use crate::PostHasteAddress;
+use crate::handler::Postmaster;
+use crate::latency::{LatencyHistograms, Timestamp, Timestamped};
 #[cfg(target_os = "none")]
-use embassy_sync::channel::DynamicReceiver as Receiver;
+use crate::mailbox::ReceiveQueue;
+use core::any::TypeId;
+#[cfg(target_os = "none")]
+use core::cell::Cell;
+use core::task::{Context, Poll};
+#[cfg(target_os = "none")]
+use embassy_sync::channel::{DynamicReceiver, TryReceiveError};
+#[cfg(not(target_os = "none"))]
+use portable_atomic::{AtomicBool, Ordering};
 #[cfg(not(target_os = "none"))]
-use tokio::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_os = "none"))]
+use tokio::sync::{
+    Notify,
+    mpsc::{Receiver, error::TryRecvError},
+};
+
+/// The receiving end of an Agent's mailbox.
+/// If the mailbox has several priority levels, the highest priority message waiting is always received first.
+/// With the `latency-histograms` feature enabled, call `handled()` once each message has been dealt with to record how long handling took.
+#[cfg(target_os = "none")]
+pub struct Inbox<T> {
+    source: InboxSource<T>,
+    owner: Option<Owner>,
+    latency: Option<&'static LatencyHistograms>,
+    handling_since: Cell<Timestamp>,
+}
+
+/// Whose inbox it is, as given by `Inbox::with_source()`.
+#[derive(Clone, Copy)]
+struct Owner {
+    address_type: TypeId,
+    index: usize,
+    generation: u32,
+}
+
+#[cfg(target_os = "none")]
+enum InboxSource<T> {
+    Single(DynamicReceiver<'static, T>),
+    Prioritised(&'static dyn ReceiveQueue<T>),
+}
+
+#[cfg(target_os = "none")]
+impl<T> Inbox<T> {
+    pub(crate) fn prioritised(queue: &'static dyn ReceiveQueue<T>) -> Self {
+        Self {
+            source: InboxSource::Prioritised(queue),
+            owner: None,
+            latency: None,
+            handling_since: Cell::new(Timestamp::default()),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn with_latency(self, latency: &'static LatencyHistograms) -> Self {
+        Self {
+            latency: Some(latency),
+            ..self
+        }
+    }
+
+    /// Give the inbox the source of the Agent's address, which `Context::from_inbox()` can then recreate.
+    /// Taking the source rather than the address means that only the owner of an address can pass it on.
+    #[doc(hidden)]
+    pub fn with_source<A: Postmaster>(self, source: A::Source) -> Self {
+        Self {
+            owner: Some(Owner {
+                address_type: TypeId::of::<A>(),
+                index: A::source_address(&source).index(),
+                generation: A::source_generation(&source),
+            }),
+            ..self
+        }
+    }
+
+    /// The address at which the inbox's Agent is registered.
+    /// Only known for the inboxes of Agents registered with `register_agent!()` (or `register_supervised_agent!()`).
+    pub fn address<A: PostHasteAddress + 'static>(&self) -> Option<A> {
+        self.owner::<A>().map(|(address, _)| address)
+    }
+
+    /// The address and registration of the source which the inbox was given, if it was given one of the address enum `A`.
+    pub(crate) fn owner<A: PostHasteAddress + 'static>(&self) -> Option<(A, u32)> {
+        let owner = self.owner.filter(|owner| owner.address_type == TypeId::of::<A>())?;
+        A::from_index(owner.index).map(|address| (address, owner.generation))
+    }
+
+    /// Mark the message most recently received as handled, recording how long the Agent spent handling it.
+    /// Does nothing unless the `latency-histograms` feature is enabled.
+    pub fn handled(&self) {
+        if let (Some(latency), Some(elapsed)) = (self.latency, self.handling_since.take().elapsed_us()) {
+            latency.handling.record(elapsed);
+        }
+    }
+}
 
 #[cfg(target_os = "none")]
-pub type Inbox<T> = Receiver<'static, T>;
+impl<T: Timestamped> Inbox<T> {
+    /// Receive the next message, waiting for one to arrive if the inbox is empty.
+    pub async fn receive(&self) -> T {
+        core::future::poll_fn(|cx| self.poll_receive(cx)).await
+    }
+
+    /// Receive the next message if there is one waiting, without waiting.
+    pub fn try_receive(&self) -> Result<T, TryReceiveError> {
+        self.try_receive_untimed().inspect(|message| self.note_received(message))
+    }
+
+    /// Poll for the next message, registering the current task to be woken when one arrives.
+    pub fn poll_receive(&self, cx: &mut Context<'_>) -> Poll<T> {
+        let poll = self.poll_receive_untimed(cx);
+        if let Poll::Ready(message) = &poll {
+            self.note_received(message);
+        }
+        poll
+    }
+
+    fn note_received(&self, message: &T) {
+        if let Some(latency) = self.latency {
+            if let Some(waited) = message.enqueued_at().elapsed_us() {
+                latency.queueing.record(waited);
+            }
+            self.handling_since.set(Timestamp::now());
+        }
+    }
+
+    fn try_receive_untimed(&self) -> Result<T, TryReceiveError> {
+        match &self.source {
+            InboxSource::Single(receiver) => receiver.try_receive(),
+            InboxSource::Prioritised(queue) => (0..queue.levels())
+                .rev()
+                .find_map(|level| queue.try_receive(level).ok())
+                .ok_or(TryReceiveError::Empty),
+        }
+    }
+
+    fn poll_receive_untimed(&self, cx: &mut Context<'_>) -> Poll<T> {
+        match &self.source {
+            InboxSource::Single(receiver) => receiver.poll_receive(cx),
+            InboxSource::Prioritised(queue) => (0..queue.levels())
+                .rev()
+                .find_map(|level| match queue.poll_receive(level, cx) {
+                    Poll::Ready(message) => Some(Poll::Ready(message)),
+                    Poll::Pending => None,
+                })
+                .unwrap_or(Poll::Pending),
+        }
+    }
+}
+
+#[cfg(target_os = "none")]
+impl<T> From<DynamicReceiver<'static, T>> for Inbox<T> {
+    fn from(receiver: DynamicReceiver<'static, T>) -> Self {
+        Self {
+            source: InboxSource::Single(receiver),
+            owner: None,
+            latency: None,
+            handling_since: Cell::new(Timestamp::default()),
+        }
+    }
+}
+
+/// What an Agent receives from its inbox with `Inbox::next()`.
 #[cfg(not(target_os = "none"))]
-pub type Inbox<T> = Receiver<T>;
+#[derive(Debug)]
+pub enum Delivery<T> {
+    /// A message sent to the Agent
+    Message(T),
+    /// The Postmaster is shutting down (see `postmaster::shutdown()`), and every message which was waiting in the inbox has been received.
+    /// This is the Agent's notification to save any state and tidy up.
+    Shutdown,
+    /// The Agent's address has been deregistered, and every message which was waiting in the inbox has been received.
+    Closed,
+}
+
+/// The receiving end of an Agent's mailbox.
+/// If the mailbox has several priority levels, the highest priority message waiting is always received first.
+///
+/// Once the mailbox has closed and every queued message has been received, the inbox delivers a single lifecycle notification.
+/// `next()` delivers it as `Delivery::Shutdown` if the Postmaster is shutting down, or `Delivery::Closed` if the address was deregistered.
+/// `recv()` only reports deregistration, by returning `None`; during a shutdown it simply stops returning messages, so that Agents which unwrap every message are not disturbed.
+/// Any call to `next()` or `recv()` after the notification never resolves, which tells the Postmaster that the Agent has finished.
+/// With the `latency-histograms` feature enabled, call `handled()` once each message has been dealt with to record how long handling took.
+#[cfg(not(target_os = "none"))]
+pub struct Inbox<T> {
+    levels: Box<dyn ReceiveLevels<T>>,
+    owner: Option<Owner>,
+    closed: bool,
+    idle: Arc<Notify>,
+    shutting_down: Option<&'static AtomicBool>,
+    latency: Option<&'static LatencyHistograms>,
+    handling_since: Timestamp,
+}
+
+#[cfg(not(target_os = "none"))]
+impl<T: Send + 'static> Inbox<T> {
+    #[doc(hidden)]
+    pub fn new(receiver: Receiver<T>, idle: Arc<Notify>) -> Self {
+        Self {
+            idle,
+            ..Self::prioritised(Box::new([receiver]))
+        }
+    }
+
+    /// Create an inbox which receives from a queue shared with inboxes which came before it.
+    /// A supervised Agent is restarted with such an inbox, so that messages which its previous incarnation had yet to receive are not lost.
+    #[doc(hidden)]
+    pub fn shared(receiver: Arc<Mutex<Receiver<T>>>, idle: Arc<Notify>) -> Self {
+        Self {
+            levels: Box::new(receiver),
+            idle,
+            ..Self::prioritised(Box::new([]))
+        }
+    }
+
+    pub(crate) fn prioritised(levels: Box<[Receiver<T>]>) -> Self {
+        Self {
+            levels: Box::new(levels),
+            owner: None,
+            closed: false,
+            idle: Arc::new(Notify::new()),
+            shutting_down: None,
+            latency: None,
+            handling_since: Timestamp::default(),
+        }
+    }
+
+    /// Convert each message into another type as it is received, dropping any message which doesn't convert.
+    #[doc(hidden)]
+    pub fn narrow<U: 'static>(self, narrow: fn(T) -> Option<U>) -> Inbox<U> {
+        Inbox {
+            levels: Box::new(Narrowed {
+                levels: self.levels,
+                narrow,
+            }),
+            owner: self.owner,
+            closed: self.closed,
+            idle: self.idle,
+            shutting_down: self.shutting_down,
+            latency: self.latency,
+            handling_since: self.handling_since,
+        }
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+impl<T> Inbox<T> {
+    #[doc(hidden)]
+    pub fn with_latency(self, latency: &'static LatencyHistograms) -> Self {
+        Self {
+            latency: Some(latency),
+            ..self
+        }
+    }
+
+    /// Give the inbox the source of the Agent's address, which `Context::from_inbox()` can then recreate.
+    /// Taking the source rather than the address means that only the owner of an address can pass it on.
+    #[doc(hidden)]
+    pub fn with_source<A: Postmaster>(self, source: A::Source) -> Self {
+        Self {
+            owner: Some(Owner {
+                address_type: TypeId::of::<A>(),
+                index: A::source_address(&source).index(),
+                generation: A::source_generation(&source),
+            }),
+            ..self
+        }
+    }
+
+    /// The address at which the inbox's Agent is registered.
+    /// Only known for the inboxes of Agents registered with `register_agent!()` (or `register_supervised_agent!()`).
+    pub fn address<A: PostHasteAddress + 'static>(&self) -> Option<A> {
+        self.owner::<A>().map(|(address, _)| address)
+    }
+
+    /// The address and registration of the source which the inbox was given, if it was given one of the address enum `A`.
+    pub(crate) fn owner<A: PostHasteAddress + 'static>(&self) -> Option<(A, u32)> {
+        let owner = self.owner.filter(|owner| owner.address_type == TypeId::of::<A>())?;
+        A::from_index(owner.index).map(|address| (address, owner.generation))
+    }
+
+    /// Mark the message most recently received as handled, recording how long the Agent spent handling it.
+    /// Does nothing unless the `latency-histograms` feature is enabled.
+    pub fn handled(&mut self) {
+        let handling_since = core::mem::take(&mut self.handling_since);
+        if let (Some(latency), Some(elapsed)) = (self.latency, handling_since.elapsed_us()) {
+            latency.handling.record(elapsed);
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn with_shutdown_flag(self, shutting_down: &'static AtomicBool) -> Self {
+        Self {
+            shutting_down: Some(shutting_down),
+            ..self
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn idle_signal(&self) -> Arc<Notify> {
+        self.idle.clone()
+    }
+
+    fn shutting_down(&self) -> bool {
+        self.shutting_down.is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// Tell the Postmaster that the Agent has finished, and wait to be stopped.
+    async fn finished(&mut self) -> ! {
+        self.idle.notify_one();
+        core::future::pending().await
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+impl<T: Timestamped> Inbox<T> {
+    /// Receive the next message, waiting for one to arrive if the inbox is empty.
+    /// Returns `None` once the address has been deregistered and every queued message has been received.
+    /// During a shutdown this never returns `None`: once the queued messages have been received the Agent is simply stopped (see `next()`, to be notified first).
+    pub async fn recv(&mut self) -> Option<T> {
+        match self.next().await {
+            Delivery::Message(message) => Some(message),
+            Delivery::Shutdown => self.finished().await,
+            Delivery::Closed => None,
+        }
+    }
+
+    /// Receive the next message or lifecycle notification, waiting for one to arrive if the inbox is empty.
+    pub async fn next(&mut self) -> Delivery<T> {
+        if self.closed {
+            self.finished().await;
+        }
+        match core::future::poll_fn(|cx| self.levels.poll_recv(cx)).await {
+            Some(message) => {
+                self.note_received(&message);
+                Delivery::Message(message)
+            }
+            None => {
+                self.closed = true;
+                if self.shutting_down() {
+                    Delivery::Shutdown
+                } else {
+                    Delivery::Closed
+                }
+            }
+        }
+    }
+
+    /// Receive the next message if there is one waiting, without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let message = self.levels.try_recv();
+        if let Ok(message) = &message {
+            self.note_received(message);
+        }
+        message
+    }
+
+    fn note_received(&mut self, message: &T) {
+        if let Some(latency) = self.latency {
+            if let Some(waited) = message.enqueued_at().elapsed_us() {
+                latency.queueing.record(waited);
+            }
+            self.handling_since = Timestamp::now();
+        }
+    }
+
+}
+
+/// The message queues from which an inbox receives, one per priority level.
+#[cfg(not(target_os = "none"))]
+trait ReceiveLevels<T>: Send {
+    fn try_recv(&mut self) -> Result<T, TryRecvError>;
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>>;
+}
+
+#[cfg(not(target_os = "none"))]
+impl<T: Send> ReceiveLevels<T> for Box<[Receiver<T>]> {
+    fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut error = TryRecvError::Disconnected;
+        for level in self.iter_mut().rev() {
+            match level.try_recv() {
+                Ok(message) => return Ok(message),
+                Err(TryRecvError::Empty) => error = TryRecvError::Empty,
+                Err(TryRecvError::Disconnected) => {}
+            }
+        }
+        Err(error)
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut closed = true;
+        for level in self.iter_mut().rev() {
+            match level.poll_recv(cx) {
+                Poll::Ready(Some(message)) => return Poll::Ready(Some(message)),
+                Poll::Ready(None) => {}
+                Poll::Pending => closed = false,
+            }
+        }
+        if closed { Poll::Ready(None) } else { Poll::Pending }
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+impl<T: Send> ReceiveLevels<T> for Arc<Mutex<Receiver<T>>> {
+    fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.lock().unwrap().try_recv()
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.lock().unwrap().poll_recv(cx)
+    }
+}
+
+/// Message queues whose messages are converted into another type as they are received (see `Inbox::narrow()`).
+#[cfg(not(target_os = "none"))]
+struct Narrowed<R, T> {
+    levels: Box<dyn ReceiveLevels<R>>,
+    narrow: fn(R) -> Option<T>,
+}
+
+#[cfg(not(target_os = "none"))]
+impl<R, T> ReceiveLevels<T> for Narrowed<R, T> {
+    fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        loop {
+            if let Some(message) = (self.narrow)(self.levels.try_recv()?) {
+                return Ok(message);
+            }
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        loop {
+            match self.levels.poll_recv(cx) {
+                Poll::Ready(Some(message)) => {
+                    if let Some(message) = (self.narrow)(message) {
+                        return Poll::Ready(Some(message));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+impl<T: Send + 'static> From<Receiver<T>> for Inbox<T> {
+    fn from(receiver: Receiver<T>) -> Self {
+        Self::new(receiver, Arc::new(Notify::new()))
+    }
+}
 
 #[allow(async_fn_in_trait)]
 pub trait Agent {
@@ -16,5 +462,7 @@ pub trait Agent {
 
     async fn create(address: Self::Address, config: Self::Config) -> Self;
 
+    /// The Agent's main loop.
+    /// On tokio, an Agent which needs to save its state when the system shuts down should receive with `inbox.next()` and tidy up on `Delivery::Shutdown` (see `postmaster::shutdown()`).
     async fn run(self, inbox: Inbox<Self::Message>) -> !;
 }
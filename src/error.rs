@@ -1,19 +1,60 @@
 #[cfg(target_os = "none")]
 pub mod imports {
     pub use embassy_executor::SpawnError;
-    pub use embassy_sync::{channel::TrySendError, mutex::TryLockError};
+    pub use embassy_sync::channel::TrySendError;
     pub use embassy_time::TimeoutError;
 }
 #[cfg(not(target_os = "none"))]
 pub mod imports {
-    pub use tokio::sync::{TryLockError, mpsc::error::SendError, mpsc::error::TrySendError};
+    pub use tokio::sync::mpsc::error::{SendError, TrySendError};
 }
 
 use imports::*;
 
-/// Enumeration of potential errors which the Postmaster may encounter
-#[derive(Debug)]
-pub enum PostmasterError {
+/// Declares `PostmasterError` along with the list of its variants and its `defmt` implementation, so that they cannot drift apart.
+/// `FailureCounts` relies on `PostmasterError::ALL[error as usize] == error`.
+macro_rules! postmaster_errors {
+    ($(
+        $(#[doc = $doc:literal])*
+        $(#[deprecated($($deprecation:tt)*)])?
+        $(#[cfg($cfg:meta)])?
+        $variant:ident,
+    )*) => {
+        /// Enumeration of potential errors which the Postmaster may encounter
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum PostmasterError {
+            $(
+                $(#[doc = $doc])*
+                $(#[deprecated($($deprecation)*)])?
+                $(#[cfg($cfg)])?
+                $variant,
+            )*
+        }
+
+        #[allow(deprecated)]
+        impl PostmasterError {
+            /// Every variant, in the order in which they are declared.
+            pub const ALL: &'static [PostmasterError] = &[$($(#[cfg($cfg)])? Self::$variant,)*];
+
+            /// The number of variants.
+            pub const COUNT: usize = Self::ALL.len();
+        }
+
+        // Generated rather than derived, as the derived implementation would trip the deprecation lint on `TryLockFailed`
+        #[cfg(all(feature = "defmt", target_os = "none"))]
+        #[allow(deprecated)]
+        impl defmt::Format for PostmasterError {
+            fn format(&self, f: defmt::Formatter) {
+                let name = match self {
+                    $($(#[cfg($cfg)])? Self::$variant => stringify!($variant),)*
+                };
+                defmt::write!(f, "{=str}", name)
+            }
+        }
+    };
+}
+
+postmaster_errors! {
     /// The address specified has already been assigned
     AddressAlreadyTaken,
     /// No recipient has been registered at the specified address
@@ -21,7 +62,8 @@ pub enum PostmasterError {
     /// The timeout was triggered while attempting to send a message
     Timeout,
     /// Postmaster was unable to acquire a lock on the Senders when `postmaster::try_send()` was called.
-    /// This may happen if another task is waiting to send a message at the same time.
+    /// The routing table no longer uses a lock, so this error is never returned.
+    #[deprecated(note = "sends no longer lock the routing table, so this error is never returned")]
     TryLockFailed,
     /// The Receiver for the specified address has closed (gone out of scope).
     #[cfg(not(target_os = "none"))]
@@ -34,6 +76,11 @@ pub enum PostmasterError {
     /// Try increasing the DELAYED_MESSAGE_POOL_SIZE environment variable (default is 8).
     #[cfg(target_os = "none")]
     DelayedMessagePoolFull,
+    /// Postmaster was unable to spawn a task to handle a periodic message.
+    /// This is most likely caused by the task pool being too small.
+    /// Try increasing the PERIODIC_MESSAGE_POOL_SIZE environment variable (default is 4).
+    #[cfg(target_os = "none")]
+    PeriodicMessagePoolFull,
     /// A reference to the spawner has not yet been passed to the Postmaster.
     /// This is usually achieved automatically when `register_agent!()` is called.
     /// If you have not yet registered any Agents, you can call `postmaster::set_spawner()` before attempting to send the delayed message.
@@ -44,12 +91,82 @@ pub enum PostmasterError {
     /// in the post-haste source code.
     #[cfg(target_os = "none")]
     SpawnFailed,
+    /// The recipient of a request dropped its `ReplyHandle` without sending a reply.
+    ReplyDropped,
+    /// A reply could not be delivered because the requester is no longer waiting for it.
+    /// This is most likely because the request's timeout expired before the reply was sent.
+    RequesterGone,
+    /// Postmaster was unable to reserve a slot to receive the reply to a request.
+    /// This is most likely caused by too many requests awaiting replies at the same time.
+    /// Try increasing the REPLY_POOL_SIZE environment variable (default is 4).
+    #[cfg(target_os = "none")]
+    ReplyPoolFull,
+    /// The delayed message was cancelled before its delay elapsed.
+    Cancelled,
+    /// The delay has already elapsed and the message has been handed over for delivery,
+    /// so it can no longer be cancelled or rescheduled.
+    AlreadyFired,
+    /// The Postmaster is shutting down (see `postmaster::shutdown()`), so it is no longer accepting messages or registrations.
+    #[cfg(not(target_os = "none"))]
+    ShuttingDown,
+    /// The recipient only receives some of the payloads (see `post_haste::PayloadVariant`), and the payload sent is not one of them.
+    UnsupportedPayload,
+    /// Every one of the Agent's named timers is pending (see `Context::start_timer()`).
+    /// Cancel one of them, or wait for one to fire, before starting another.
+    TimerSlotsFull,
+    /// A periodic message was given a period of zero, which would deliver it endlessly without pause.
+    ZeroPeriod,
+    /// The `SourceToken` was handed out for an earlier registration of its address, which has since been deregistered.
+    StaleSource,
+    /// `postmaster::replay()` was given an accelerated timing whose factor is not a finite number greater than zero.
+    #[cfg(not(target_os = "none"))]
+    InvalidReplayTiming,
 }
 
-impl From<TryLockError> for PostmasterError {
-    fn from(_: TryLockError) -> Self {
-        Self::TryLockFailed
+/// A tally of failures, broken down by the error which caused them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FailureCounts {
+    counts: [usize; PostmasterError::COUNT],
+}
+
+impl FailureCounts {
+    #[doc(hidden)]
+    pub fn new(counts: [usize; PostmasterError::COUNT]) -> Self {
+        Self { counts }
+    }
+
+    /// The number of failures caused by the given error.
+    pub fn get(&self, error: PostmasterError) -> usize {
+        self.counts[error as usize]
+    }
+
+    /// The total number of failures.
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
     }
+
+    /// Iterate over the errors which have caused failures, along with the number of failures each has caused.
+    pub fn iter(&self) -> impl Iterator<Item = (PostmasterError, usize)> + '_ {
+        PostmasterError::ALL
+            .iter()
+            .copied()
+            .zip(self.counts)
+            .filter(|(_, count)| *count > 0)
+    }
+}
+
+/// Contains diagnostic information for the Postmaster.
+/// Obtained by calling postmaster::get_diagnostics()
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(all(feature = "defmt", target_os = "none"), derive(defmt::Format))]
+pub struct Diagnostics {
+    /// The number of messages successfully sent since the Postmaster was initialised.
+    pub messages_sent: usize,
+    /// The number of messages which could not be sent since the Postmaster was initialised.
+    pub send_failures: usize,
+    /// The number of delayed messages which could not be delivered once their delay had elapsed.
+    /// These failures are also included in `send_failures`.
+    pub delayed_send_failures: usize,
 }
 
 impl<T> From<TrySendError<T>> for PostmasterError {
@@ -78,3 +195,28 @@ impl<T> From<SendError<T>> for PostmasterError {
         Self::ReceiverClosed
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{FailureCounts, PostmasterError};
+
+    #[test]
+    fn all_is_indexed_by_discriminant() {
+        for (index, error) in PostmasterError::ALL.iter().enumerate() {
+            assert_eq!(*error as usize, index);
+        }
+        assert_eq!(
+            PostmasterError::ALL.len(),
+            PostmasterError::InvalidReplayTiming as usize + 1
+        );
+    }
+
+    #[test]
+    fn failure_counts_are_reported_against_their_error() {
+        let counts = FailureCounts::new(core::array::from_fn(|index| index));
+        for (index, error) in PostmasterError::ALL.iter().enumerate() {
+            assert_eq!(counts.get(*error), index);
+        }
+        assert!(counts.iter().all(|(error, count)| error as usize == count));
+    }
+}
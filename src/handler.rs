@@ -0,0 +1,255 @@
+//! An alternative to implementing `Agent` by hand, for Agents whose main loop does nothing but handle each message in turn.
+
+use core::marker::PhantomData;
+
+use crate::PostHasteAddress;
+use crate::PostmasterError;
+#[cfg(not(target_os = "none"))]
+use crate::agent::Delivery;
+use crate::agent::{Agent, Inbox};
+use crate::dependencies::Duration;
+use crate::latency::Timestamped;
+use crate::payload::Narrow;
+use crate::trace::Traced;
+
+/// The number of named timers which a `Context` can have pending at once (see `Context::start_timer()`).
+pub const TIMER_SLOTS: usize = 4;
+
+/// Connects an address enum to the Postmaster generated for it, so that a `Context` can send messages.
+/// Implemented for the address enum by `init_postmaster!()`.
+#[doc(hidden)]
+#[allow(async_fn_in_trait)]
+pub trait Postmaster: PostHasteAddress + Copy + 'static {
+    type Payload;
+    type Timer;
+    /// What a `Context` holds to send from an address: the address itself, or its `SourceToken` if the Postmaster authenticates sources.
+    type Source;
+
+    /// Recreate the source which an inbox was given by `Inbox::with_source()`.
+    /// Only this crate can create the `Authority`, so a source can't be minted for an address which the caller doesn't own.
+    fn authorise(authority: Authority<Self>, address: Self, generation: u32) -> Self::Source;
+
+    fn source_address(source: &Self::Source) -> Self;
+
+    /// The registration of the address which the source was handed out for.
+    fn source_generation(source: &Self::Source) -> u32;
+
+    async fn send(destination: Self, source: &Self::Source, payload: Self::Payload) -> Result<(), PostmasterError>;
+
+    async fn send_after(
+        destination: Self,
+        source: &Self::Source,
+        payload: Self::Payload,
+        delay: Duration,
+    ) -> Result<Self::Timer, PostmasterError>;
+
+    fn cancel(timer: &Self::Timer) -> Result<(), PostmasterError>;
+
+    fn has_fired(timer: &Self::Timer) -> bool;
+}
+
+/// Permission to call `Postmaster::authorise()`, which only `Context::from_inbox()` grants, for an inbox which was given a source of the same address enum.
+#[doc(hidden)]
+pub struct Authority<A>(PhantomData<A>);
+
+/// Implemented by the `postmaster::Message` generated by `init_postmaster!()`, so that `Context::reply()` can find where a message came from.
+pub trait Envelope<A> {
+    fn source(&self) -> A;
+}
+
+/// An Agent's view of the Postmaster, which sends messages with the Agent's own address as their source.
+/// A `MessageHandler` is handed one; any other Agent can make one from its inbox with `Context::from_inbox()`.
+/// It is also generated as `postmaster::AgentContext`.
+pub struct Context<A: Postmaster> {
+    source: A::Source,
+    timers: [Option<(&'static str, A::Timer)>; TIMER_SLOTS],
+}
+
+impl<A: Postmaster> Context<A> {
+    /// Make a context which sends from the given source.
+    /// This is the Agent's address, or its `postmaster::SourceToken` if the Postmaster was initialised with `sources: authenticated`.
+    pub fn new(source: A::Source) -> Self {
+        Self {
+            source,
+            timers: core::array::from_fn(|_| None),
+        }
+    }
+
+    /// Make a context for the Agent which owns the inbox.
+    /// Returns `None` if the inbox was not created by `register_agent!()` (or `register_supervised_agent!()`), as only then is it given the Agent's source.
+    pub fn from_inbox<T>(inbox: &Inbox<T>) -> Option<Self> {
+        inbox
+            .owner()
+            .map(|(address, generation)| Self::new(A::authorise(Authority(PhantomData), address, generation)))
+    }
+
+    /// The address at which the Agent is registered, which is given as the source of the messages it sends.
+    pub fn address(&self) -> A {
+        A::source_address(&self.source)
+    }
+
+    /// What the context sends from, which can be given as the source to the Postmaster's sending functions (e.g. `postmaster::send(destination, ctx.source(), payload)`).
+    pub fn source(&self) -> &A::Source {
+        &self.source
+    }
+
+    /// Give up the context, returning what it sends from.
+    /// An Agent can pass this to `postmaster::deregister()` to retire from its address.
+    pub fn into_source(self) -> A::Source {
+        self.source
+    }
+
+    /// Send a message using the Postmaster's default timeout (see `postmaster::send()`).
+    pub async fn send_to(&self, destination: A, payload: impl Into<A::Payload>) -> Result<(), PostmasterError> {
+        A::send(destination, &self.source, payload.into()).await
+    }
+
+    /// Send a message back to the source of a message which the Agent received.
+    /// To answer a request, use the `ReplyHandle` in the message's `reply_to` field instead.
+    pub async fn reply<M: Envelope<A>>(
+        &self,
+        message: &M,
+        payload: impl Into<A::Payload>,
+    ) -> Result<(), PostmasterError> {
+        self.send_to(message.source(), payload).await
+    }
+
+    /// Send the Agent a message once the delay has elapsed (see `MessageBuilder::with_delay()`).
+    /// The returned handle can be used to cancel or reschedule the message.
+    pub async fn send_self_after(
+        &self,
+        delay: Duration,
+        payload: impl Into<A::Payload>,
+    ) -> Result<A::Timer, PostmasterError> {
+        A::send_after(self.address(), &self.source, payload.into(), delay).await
+    }
+
+    /// Start a named timer, which sends the Agent the payload once the delay has elapsed.
+    /// Any timer of the same name which is still pending is cancelled, so restarting a timer never delivers the payload twice.
+    /// Fails with `PostmasterError::TimerSlotsFull` if `TIMER_SLOTS` other timers are pending.
+    pub async fn start_timer(
+        &mut self,
+        name: &'static str,
+        delay: Duration,
+        payload: impl Into<A::Payload>,
+    ) -> Result<(), PostmasterError> {
+        self.cancel_timer(name);
+        self.forget_fired_timers();
+        let slot = self
+            .timers
+            .iter()
+            .position(Option::is_none)
+            .ok_or(PostmasterError::TimerSlotsFull)?;
+        let timer = self.send_self_after(delay, payload).await?;
+        self.timers[slot] = Some((name, timer));
+        Ok(())
+    }
+
+    /// Cancel a named timer, returning whether it was still pending.
+    pub fn cancel_timer(&mut self, name: &'static str) -> bool {
+        self.timers
+            .iter_mut()
+            .filter(|slot| matches!(slot, Some((timer_name, _)) if *timer_name == name))
+            .filter_map(Option::take)
+            .fold(false, |cancelled, (_, timer)| A::cancel(&timer).is_ok() || cancelled)
+    }
+
+    /// Check whether a named timer has been started and has not yet fired or been cancelled.
+    pub fn timer_pending(&self, name: &'static str) -> bool {
+        self.timers
+            .iter()
+            .flatten()
+            .any(|(timer_name, timer)| *timer_name == name && !A::has_fired(timer))
+    }
+
+    /// Release the handles of timers which have fired.
+    /// On Embassy, each handle holds a slot in the delayed message pool until it is dropped.
+    pub(crate) fn forget_fired_timers(&mut self) {
+        for slot in &mut self.timers {
+            if slot.as_ref().is_some_and(|(_, timer)| A::has_fired(timer)) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+/// Implement this instead of `Agent` to have the main loop written for you.
+/// Every type which implements `MessageHandler` implements `Agent`, with a main loop which calls `on_start()` once, then passes each message received to `handle()`.
+/// Each message is handled within the span in which it was sent (see `TraceContext::instrument()`), so messages sent in response are placed in the same trace.
+/// The Agent must be registered with `register_agent!()` (or `register_supervised_agent!()`), which gives its inbox the source to put in the `Context`.
+/// The `Context` sends messages with that address as their source, and keeps the Agent's named timers.
+///
+/// Messages arrive in the inbox as `Received`, and are narrowed into `Message` before being handled.
+/// Usually both are the same `postmaster::Message<T>`, in which case sending the Agent any payload which doesn't carry a `T` fails with `PostmasterError::UnsupportedPayload`.
+/// Alternatively, set `Received` to `postmaster::Message` to accept every payload, with those which don't carry a `T` being passed to `on_unknown()`.
+#[allow(async_fn_in_trait)]
+pub trait MessageHandler: Sized {
+    type Address: Postmaster;
+    type Config;
+    /// The messages which `handle()` receives.
+    type Message;
+    /// The messages which arrive in the Agent's inbox.
+    type Received: Narrow<Self::Message> + Timestamped + Traced;
+
+    async fn create(address: Self::Address, config: Self::Config) -> Self;
+
+    /// Called once, before the first message is handled.
+    async fn on_start(&mut self, ctx: &mut Context<Self::Address>) {
+        let _ = ctx;
+    }
+
+    async fn handle(&mut self, ctx: &mut Context<Self::Address>, message: Self::Message);
+
+    /// Called with each message which arrives in the inbox but does not narrow into `Message`.
+    /// Such messages are ignored by default.
+    async fn on_unknown(&mut self, ctx: &mut Context<Self::Address>, message: Self::Received) {
+        let _ = (ctx, message);
+    }
+
+    /// Called once when the Postmaster shuts down (see `postmaster::shutdown()`), after every message which was waiting in the inbox has been handled.
+    /// This is the place to save any state, as the Agent is stopped as soon as it returns.
+    #[cfg(not(target_os = "none"))]
+    async fn on_shutdown(&mut self, ctx: &mut Context<Self::Address>) {
+        let _ = ctx;
+    }
+}
+
+impl<T: MessageHandler> Agent for T {
+    type Address = T::Address;
+    type Message = T::Received;
+    type Config = T::Config;
+
+    async fn create(address: Self::Address, config: Self::Config) -> Self {
+        <T as MessageHandler>::create(address, config).await
+    }
+
+    async fn run(mut self, mut inbox: Inbox<Self::Message>) -> ! {
+        let mut ctx = Context::from_inbox(&inbox).expect("a MessageHandler must be registered with register_agent!()");
+        self.on_start(&mut ctx).await;
+        loop {
+            #[cfg(not(target_os = "none"))]
+            let message = match inbox.next().await {
+                Delivery::Message(message) => message,
+                Delivery::Shutdown => {
+                    self.on_shutdown(&mut ctx).await;
+                    continue;
+                }
+                Delivery::Closed => continue,
+            };
+            #[cfg(target_os = "none")]
+            let message = inbox.receive().await;
+            // Handled within the span in which it was sent, so that anything sent in response joins the same trace
+            let trace = message.trace().clone();
+            trace
+                .instrument(async {
+                    match message.narrow() {
+                        Ok(message) => self.handle(&mut ctx, message).await,
+                        Err(message) => self.on_unknown(&mut ctx, message).await,
+                    }
+                })
+                .await;
+            inbox.handled();
+            ctx.forget_fired_timers();
+        }
+    }
+}
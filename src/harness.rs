@@ -0,0 +1,31 @@
+//! A simulated clock for testing Agents deterministically, alongside the `postmaster::harness` module generated by `init_postmaster!()`.
+//! Requires the `test-harness` feature, which enables tokio's `test-util` feature.
+
+use crate::dependencies::{Duration, Instant, time};
+
+/// Tokio's clock, paused so that time only moves when the test advances it, or when every task is waiting on a timer (at which point it jumps straight to the next one).
+/// Delayed and periodic messages, send timeouts and the Harness's assertion windows all run on this clock, so a test behaves the same way on every run.
+pub struct SimulatedClock {
+    started: Instant,
+}
+
+impl SimulatedClock {
+    /// Pause tokio's clock.
+    /// Must be called from a current-thread runtime (which is what `#[tokio::test]` provides), in a test which does not already start paused.
+    pub fn start() -> Self {
+        time::pause();
+        Self {
+            started: Instant::now(),
+        }
+    }
+
+    /// How much virtual time has passed since the clock was started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Move the clock forward, firing any timers which fall due along the way.
+    pub async fn advance(&self, duration: Duration) {
+        time::advance(duration).await
+    }
+}
@@ -0,0 +1,179 @@
+//! Message latency and handling-time histograms.
+//! Timestamps are only taken, and histograms only recorded, when the `latency-histograms` feature is enabled.
+//! Without it, the types in this module are zero-sized and every operation on them does nothing.
+
+#[cfg(feature = "latency-histograms")]
+use crate::dependencies::{AtomicU32, Instant};
+#[cfg(feature = "latency-histograms")]
+use core::sync::atomic::Ordering;
+
+/// The number of buckets in each histogram.
+pub const BUCKET_COUNT: usize = 7;
+
+/// The upper bound (exclusive) of each bucket in microseconds, apart from the last bucket which has no upper bound.
+pub const BUCKET_BOUNDS_US: [u64; BUCKET_COUNT - 1] = [10, 100, 1_000, 10_000, 100_000, 1_000_000];
+
+/// The moment a message was pushed into a mailbox.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timestamp {
+    #[cfg(feature = "latency-histograms")]
+    at: Option<Instant>,
+}
+
+impl Timestamp {
+    /// Take a timestamp of the current moment.
+    pub fn now() -> Self {
+        Self {
+            #[cfg(feature = "latency-histograms")]
+            at: Some(Instant::now()),
+        }
+    }
+
+    /// The number of microseconds since the timestamp was taken.
+    /// This is `None` if no timestamp was taken, including whenever the `latency-histograms` feature is disabled.
+    pub fn elapsed_us(&self) -> Option<u64> {
+        #[cfg(feature = "latency-histograms")]
+        return self.at.map(|at| at.elapsed().as_micros() as u64);
+        #[cfg(not(feature = "latency-histograms"))]
+        None
+    }
+}
+
+/// Implemented by message types which carry the time at which they were pushed into a mailbox.
+pub trait Timestamped {
+    fn enqueued_at(&self) -> Timestamp;
+}
+
+/// A histogram of durations with fixed buckets (see `BUCKET_BOUNDS_US`).
+#[derive(Default)]
+pub struct Histogram {
+    #[cfg(feature = "latency-histograms")]
+    buckets: [AtomicU32; BUCKET_COUNT],
+}
+
+impl Histogram {
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(feature = "latency-histograms")]
+            buckets: [const { AtomicU32::new(0) }; BUCKET_COUNT],
+        }
+    }
+
+    /// Add a duration in microseconds to the histogram.
+    pub fn record(&self, duration_us: u64) {
+        #[cfg(feature = "latency-histograms")]
+        {
+            let bucket = BUCKET_BOUNDS_US
+                .iter()
+                .position(|bound| duration_us < *bound)
+                .unwrap_or(BUCKET_COUNT - 1);
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        #[cfg(not(feature = "latency-histograms"))]
+        let _ = duration_us;
+    }
+
+    /// Empty every bucket.
+    pub fn clear(&self) {
+        #[cfg(feature = "latency-histograms")]
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Take a copy of the current bucket counts.
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            #[cfg(feature = "latency-histograms")]
+            counts: core::array::from_fn(|bucket| self.buckets[bucket].load(Ordering::Relaxed)),
+            #[cfg(not(feature = "latency-histograms"))]
+            counts: [0; BUCKET_COUNT],
+        }
+    }
+}
+
+/// The bucket counts of a histogram at a moment in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    /// The number of durations recorded in each bucket.
+    pub counts: [u32; BUCKET_COUNT],
+}
+
+impl HistogramSnapshot {
+    /// The total number of durations recorded.
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    /// Iterate over the buckets, giving the upper bound of each in microseconds (`None` for the last bucket) along with its count.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<u64>, u32)> + '_ {
+        BUCKET_BOUNDS_US
+            .iter()
+            .copied()
+            .map(Some)
+            .chain([None])
+            .zip(self.counts)
+    }
+}
+
+/// The latency histograms which the Postmaster maintains for each address.
+#[derive(Default)]
+pub struct LatencyHistograms {
+    /// How long messages wait in the address's mailbox before being received.
+    pub queueing: Histogram,
+    /// How long the Agent at the address spends handling each message (see `Inbox::handled()`).
+    pub handling: Histogram,
+}
+
+impl LatencyHistograms {
+    pub const fn new() -> Self {
+        Self {
+            queueing: Histogram::new(),
+            handling: Histogram::new(),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.queueing.clear();
+        self.handling.clear();
+    }
+}
+
+#[cfg(all(test, feature = "latency-histograms"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_bound_starts_the_next_bucket() {
+        let histogram = Histogram::new();
+        for duration_us in [0, 9, 10, 99, 100, 999_999, 1_000_000, u64::MAX] {
+            histogram.record(duration_us);
+        }
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.counts, [2, 2, 1, 0, 0, 1, 2]);
+        assert_eq!(snapshot.total(), 8);
+    }
+
+    #[test]
+    fn buckets_are_listed_with_their_upper_bounds() {
+        let histogram = Histogram::new();
+        histogram.record(50);
+        histogram.record(5_000_000);
+        let buckets: Vec<_> = histogram.snapshot().buckets().collect();
+        assert_eq!(
+            buckets,
+            [
+                (Some(10), 0),
+                (Some(100), 1),
+                (Some(1_000), 0),
+                (Some(10_000), 0),
+                (Some(100_000), 0),
+                (Some(1_000_000), 0),
+                (None, 1),
+            ]
+        );
+
+        histogram.clear();
+        assert_eq!(histogram.snapshot().total(), 0);
+    }
+}
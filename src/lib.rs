@@ -1,16 +1,38 @@
 #![cfg_attr(target_os = "none", no_std)]
 
+pub mod address;
 pub mod agent;
 pub mod error;
+pub mod handler;
+#[cfg(all(feature = "test-harness", not(target_os = "none")))]
+pub mod harness;
+pub mod latency;
+#[doc(hidden)]
+pub mod log;
+pub mod mailbox;
+pub mod payload;
+pub mod periodic;
+pub mod record;
+pub mod routing;
+#[cfg(not(target_os = "none"))]
+pub mod supervision;
+pub mod tap;
+pub mod trace;
 
 #[cfg(not(target_os = "none"))]
 pub mod async_runtime_dependencies {
     pub use once_cell::sync::Lazy;
+    pub use std::sync::Arc;
     pub use tokio::sync::Mutex;
-    pub use tokio::sync::mpsc::{Receiver, Sender, channel};
+    pub use tokio::sync::mpsc::{
+        Receiver, Sender, UnboundedReceiver, UnboundedSender, channel, unbounded_channel,
+    };
+    pub use tokio::sync::oneshot;
+    pub use tokio::sync::watch;
+    pub use tokio::sync::Notify;
     pub use tokio::task;
     pub use tokio::time;
-    pub use tokio::time::Duration;
+    pub use tokio::time::{Duration, Instant};
 }
 #[cfg(target_os = "none")]
 pub mod async_runtime_dependencies {
@@ -19,39 +41,54 @@ pub mod async_runtime_dependencies {
         blocking_mutex::raw::NoopRawMutex,
         channel::{Channel, DynamicSender},
         mutex::Mutex,
+        signal::Signal,
     };
-    pub use embassy_time::{Duration, Timer, WithTimeout};
+    pub use embassy_time::{Duration, Instant, Timer, WithTimeout};
 }
 pub mod dependencies {
     pub use crate::async_runtime_dependencies::*;
     pub use const_env::env_item;
-    pub use portable_atomic::{AtomicU32, AtomicUsize};
+    pub use portable_atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicUsize};
 }
-pub use error::PostmasterError;
+pub use address::{PostHasteAddress, PostHasteTopic};
+pub use error::{Diagnostics, FailureCounts, PostmasterError};
+pub use mailbox::Priority;
+pub use payload::PayloadVariant;
+pub use periodic::MissedTickBehaviour;
+pub use post_haste_derive::{PostHasteAddress, PostHastePayload, PostHasteTopic};
 
 /// Initialise the Postmaster for use in your project.
 /// As the code for the Postmaster is no_std, it requires information about the project.
 /// Therefore, the code must be generated by a macro within the host crate.
 /// This macro requires two arguments: an enum type defining the Agent addresses, and an enum type defining the message payloads.
+/// The address enum must implement `Copy` and `PostHasteAddress`, which can be derived (`#[derive(Clone, Copy, PostHasteAddress)]`) for any enum made up of unit variants.
+/// The payload enum can derive `PostHastePayload`, so that each Agent can receive just its own message type (see `postmaster::Message`).
 /// An optional third argument allows the setting of the default timeout (in microseconds) used when attempting to send a message.
 /// If this third argument is omitted, a timeout of 1 ms (1000 us) will be used.
-/// The output of the macro is the `postmaster` module, which contains the API for the Postmaster.
+/// With the `tracing` feature enabled, the address and payload enums must also implement `Debug`.
+/// Finally, the following options can be given as trailing `key: value` arguments, in any order:
+/// - `groups: { NAME: [Variant, ...], ... }` declares named groups of addresses.
+///   Each group is generated as a constant slice of addresses in the `postmaster::groups` module, ready to be passed to `postmaster::multicast()`.
+/// - `topics: TopicEnum` enables publish/subscribe messaging, using the variants of the given enum (which must implement `Copy` and `PostHasteTopic`, which can also be derived) as topics.
+/// - `sources: authenticated` requires every message to be sent from a `postmaster::SourceToken`, which is only handed out when a mailbox is registered, rather than from a bare address.
+///   The source of every delivered message is then guaranteed to be authentic.
 ///
-/// # Notes
-/// The logic generated by this macro relies on the (currently) unstable feature `variant_count`.
-/// Therefore the project must be built with the nightly compiler and you will need to include this feature at the top of the file (see example).
+/// The output of the macro is the `postmaster` module, which contains the API for the Postmaster.
+/// The module can be given a different name by starting the arguments with `name = module_name`.
+/// Each call to the macro generates an independent Postmaster, so a binary can host several isolated subsystems, each with its own addresses and payloads.
+/// The macro must be called at the root of the crate.
 ///
 /// # Examples
 /// ```rust
-/// #![feature(variant_count)]
-///
-/// use post_haste::init_postmaster;
+/// use post_haste::{PostHasteAddress, init_postmaster};
 ///
+/// #[derive(Debug, Clone, Copy, PostHasteAddress)]
 /// enum Address {
 ///   AgentOne,
 ///   AgentTwo,
 /// }
 ///
+/// #[derive(Debug)]
 /// enum Payloads {
 ///   Hello,
 ///   AByte(u8),
@@ -60,24 +97,222 @@ pub use error::PostmasterError;
 /// }
 ///
 /// init_postmaster!(Address, Payloads);
+/// # fn main() {}
+/// ```
+///
+/// Declaring address groups and topics (with the default timeout):
+/// ```rust
+/// use post_haste::{PostHasteAddress, PostHasteTopic, init_postmaster};
+///
+/// #[derive(Debug, Clone, Copy, PostHasteAddress)]
+/// enum Address {
+///   Display,
+///   Logger,
+///   Sensor,
+/// }
+///
+/// #[derive(Debug, Clone)]
+/// enum Payloads {
+///   Shutdown,
+///   Reading(u16),
+/// }
+///
+/// #[derive(Clone, Copy, PostHasteTopic)]
+/// enum Topic {
+///   SensorReadings,
+/// }
+///
+/// init_postmaster!(Address, Payloads, groups: { OUTPUTS: [Display, Logger] }, topics: Topic);
+/// # fn main() {}
+/// ```
+///
+/// A second, independent Postmaster in a module named `telemetry`, with a timeout of 5 ms:
+/// ```rust
+/// use post_haste::{PostHasteAddress, init_postmaster};
+///
+/// #[derive(Debug, Clone, Copy, PostHasteAddress)]
+/// enum TelemetryAddress {
+///   Uplink,
+///   Recorder,
+/// }
+///
+/// #[derive(Debug)]
+/// enum TelemetryPayloads {
+///   Sample(u16),
+/// }
+///
+/// init_postmaster!(name = telemetry, TelemetryAddress, TelemetryPayloads, 5000);
+/// # fn main() {}
+/// ```
+///
+/// Testing an Agent on its own with the generated `postmaster::harness` (tokio only), in virtual time:
+/// ```rust
+/// use post_haste::agent::{Agent, Inbox};
+/// use post_haste::{PostHasteAddress, init_postmaster};
+/// use std::time::Duration;
+///
+/// #[derive(Debug, Clone, Copy, PostHasteAddress)]
+/// enum Address {
+///   Ponger,
+///   Main,
+/// }
+///
+/// #[derive(Debug)]
+/// enum Payloads {
+///   Ping,
+///   Pong,
+/// }
+///
+/// init_postmaster!(Address, Payloads);
+///
+/// struct PongAgent;
+///
+/// impl Agent for PongAgent {
+///   type Address = Address;
+///   type Message = postmaster::Message;
+///   type Config = ();
+///
+///   async fn create(_address: Self::Address, _config: Self::Config) -> Self {
+///     Self
+///   }
+///
+///   async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+///     loop {
+///       let message = inbox.recv().await.unwrap();
+///       if let Payloads::Ping = message.payload {
+///         postmaster::send(message.source, Address::Ponger, Payloads::Pong).await.unwrap();
+///       }
+///     }
+///   }
+/// }
+///
+/// // In a test, this would be `#[tokio::test(start_paused = true)]`
+/// #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// async fn main() {
+///   // The harness takes sole use of the Postmaster until it is dropped
+///   let mut harness = postmaster::harness::Harness::new();
+///   harness.mock(Address::Main).await.unwrap();
+///   postmaster::register_agent!(Ponger, PongAgent, ()).unwrap();
+///
+///   harness.inject(Address::Ponger, Address::Main, Payloads::Ping).await.unwrap();
+///   harness
+///     .expect_message(Address::Main, Duration::from_secs(5), |message| {
+///       matches!(message.payload, Payloads::Pong)
+///     })
+///     .await;
+///   // A minute passes in virtual time, so this returns immediately
+///   harness.expect_no_message(Address::Main, Duration::from_secs(60)).await;
+///   drop(harness);
+///
+///   // Tests which don't need the harness can take sole use of the Postmaster themselves
+///   let _isolation = postmaster::isolate();
+///   assert!(!postmaster::is_registered(Address::Ponger));
+///   postmaster::register_agent!(Ponger, PongAgent, ()).unwrap();
+/// }
 /// ```
 #[macro_export]
 #[allow(clippy::crate_in_macro_def)]
 macro_rules! init_postmaster {
 
-    ($address_enum:ty, $payload_enum:ty, $timeout_us: expr) => {
+    (@generate $name:ident, $address_enum:ident, $payload_enum:ident, $timeout_us: expr, {
+        $($group:ident: [$($member:ident),* $(,)?]),* $(,)?
+    }, [$($topic_enum:ident)?], [$($sources:ident)?]) => {
         /// API module for the Postmaster
         /// This module contains all of the functions required to pass messages between Agents, facilitated by the Postmaster.
         ///
         /// Begin by registering Agents with the `postmaster::register_agent!()` macro.
         /// Once this is done you can send the Agent a message by calling one of the messaging functions, e.g. `postmaster::send()`.
         #[allow(clippy::crate_in_macro_def)]
-        pub mod postmaster {
+        pub mod $name {
             use super::{$address_enum, $payload_enum};
-            use post_haste::PostmasterError;
+            use post_haste::{MissedTickBehaviour, PostHasteAddress as _, PostmasterError, Priority};
             use post_haste::dependencies::*;
+            #[cfg(not(target_os = "none"))]
+            use post_haste::supervision::{RestartLimit, RestartStrategy, SupervisionEvent};
+
+            const ADDRESS_COUNT: usize = <$address_enum as post_haste::PostHasteAddress>::COUNT;
+
+            /// Named groups of addresses, as declared in the call to `init_postmaster!()`.
+            /// Each group is a slice of addresses which can be passed to `postmaster::multicast()`.
+            pub mod groups {
+                use super::$address_enum;
+                $(
+                    pub const $group: &[$address_enum] = &[$(<$address_enum>::$member),*];
+                )*
+            }
+
+            /// Proof that its holder owns an address, handed out when a mailbox is registered there (see `postmaster::register()`).
+            /// A token can't be created or copied outside of the Postmaster, so a message sent with one is guaranteed to come from the owner of its source address.
+            /// The token only lasts as long as the registration it was handed out for: once the address is deregistered, sending with it fails with `PostmasterError::StaleSource`.
+            pub struct SourceToken {
+                address: $address_enum,
+                generation: u32,
+            }
+
+            impl SourceToken {
+                /// The address which the token owns.
+                pub fn address(&self) -> $address_enum {
+                    self.address
+                }
+            }
+
+            /// Anything which can be given as the source of a message.
+            /// This is a reference to a `SourceToken`, or an address unless the Postmaster was initialised with `sources: authenticated`.
+            pub trait Source: sealed::Sealed {
+                /// The address from which the message is sent.
+                fn address(&self) -> $address_enum;
+
+                /// The registration of the address which the source was handed out for, if it is a `SourceToken`.
+                #[doc(hidden)]
+                fn generation(&self) -> Option<u32> {
+                    None
+                }
+            }
+
+            impl Source for &SourceToken {
+                fn address(&self) -> $address_enum {
+                    self.address
+                }
+
+                fn generation(&self) -> Option<u32> {
+                    Some(self.generation)
+                }
+            }
 
-            const ADDRESS_COUNT: usize = core::mem::variant_count::<$address_enum>();
+            /// Anything which proves ownership of an address, so that the address can be deregistered (see `postmaster::deregister()`).
+            /// This is a `SourceToken`, which is given up by deregistering, or an address unless the Postmaster was initialised with `sources: authenticated`.
+            pub trait Owner: sealed::Sealed {
+                /// The address which is owned.
+                fn address(&self) -> $address_enum;
+
+                /// The registration of the address which the owner was handed out for, if it is a `SourceToken`.
+                #[doc(hidden)]
+                fn generation(&self) -> Option<u32> {
+                    None
+                }
+            }
+
+            impl Owner for SourceToken {
+                fn address(&self) -> $address_enum {
+                    self.address
+                }
+
+                fn generation(&self) -> Option<u32> {
+                    Some(self.generation)
+                }
+            }
+
+            /// Keeps `Source` and `Owner` from being implemented outside of the Postmaster, which would allow a source to be forged.
+            mod sealed {
+                pub trait Sealed {}
+
+                impl Sealed for super::SourceToken {}
+                impl Sealed for &super::SourceToken {}
+            }
+
+            $crate::init_postmaster!(@sources $address_enum [$($sources)?]);
+
+            $crate::init_postmaster!(@topics $address_enum, $payload_enum, [$($topic_enum)?]);
 
             /// Initialises an Agent and its message queue
             /// This macro both instantiates an Actor and kicks off its main loop.
@@ -85,29 +320,35 @@ macro_rules! init_postmaster {
             /// As well as the address and Agent type this macro also requires an instance of the Agent's associated Config type which is used during the instantiation of the Agent, and an optional queue size parameter which dictates the number of messages the Agent's message queue can hold.
             /// If no queue size parameter is given this defaults to 1, meaning that if there is already a message waiting in an Agent's queue then any attempt to send a message to the Agent will have to wait until either the queued message is received, or the send timeout is reached (in which case message sending is considered a failure).
             /// If try_send() is used to send to a full message queue, it will immediately return with failure.
-            #[macro_export]
+            ///
+            /// Alternatively, the queue size can be replaced with `priority_levels: LEVELS, capacity: CAPACITY` to give the Agent a mailbox with a separate queue of the given capacity for each priority level.
+            /// The Agent's inbox then always yields the highest priority message waiting (see `MessageBuilder::with_priority()`).
+            ///
+            /// Returns an `AgentRef`, through which the Agent can be sent messages of its own type.
             #[cfg(not(target_os = "none"))]
             macro_rules! _register_agent {
-                ($agent_address:ident, $agent:ty, $config:expr, $queue_size: expr) => {{
-                    use crate::postmaster::Message;
+                ($agent_address:ident, $agent:ty, $config:expr, priority_levels: $levels:expr, capacity: $capacity:expr) => {{
                     use post_haste::agent::Agent;
-                    use post_haste::dependencies::*;
-                    struct Mailbox {
-                        pub sender: Sender<Message>,
-                        pub receiver: Receiver<Message>,
-                    }
-                    let (sender, receiver) = channel::<Message>($queue_size);
+                    use post_haste::mailbox::Mailbox;
+                    let (mailbox, inbox) = Mailbox::prioritised($levels, $capacity);
+                    let inbox = inbox.narrow(<<$agent as Agent>::Message>::narrow);
 
-                    let agent = <$agent>::create(<$address_enum>::$agent_address, $config).await;
-                    postmaster::register(<$address_enum>::$agent_address, sender).await.inspect(|_|{
-
-                        tokio::task::spawn(async move {
-                            agent.run(receiver).await;
+                    let agent = <$agent as Agent>::create(<$address_enum>::$agent_address, $config).await;
+                    crate::$name::register_mailbox(<$address_enum>::$agent_address, mailbox, <<$agent as Agent>::Message>::accepts).await.map(|token| {
+                        let source = crate::$name::context_source(token);
+                        let idle = inbox.idle_signal();
+                        let task = tokio::task::spawn(async move {
+                            agent.run(inbox.with_latency(crate::$name::latency_histograms(<$address_enum>::$agent_address)).with_source::<$address_enum>(source).with_shutdown_flag(crate::$name::shutdown_flag())).await;
                         });
+                        crate::$name::track_agent(<$address_enum>::$agent_address, task, idle);
+                        crate::$name::AgentRef::<$agent>::new(<$address_enum>::$agent_address)
                     })
                 }};
+                ($agent_address:ident, $agent:ty, $config:expr, $queue_size: expr) => {
+                    crate::$name::register_agent!($agent_address, $agent, $config, priority_levels: 1, capacity: $queue_size)
+                };
                 ($agent_address:ident, $agent:ty, $config:expr) => {
-                    crate::postmaster::register_agent!($agent_address, $agent, $config, 1)
+                    crate::$name::register_agent!($agent_address, $agent, $config, 1)
                 };
             }
 
@@ -117,43 +358,138 @@ macro_rules! init_postmaster {
             /// As well as the address and Agent type this macro also requires an instance of the Agent's associated Config type which is used during the instantiation of the Agent, and an optional queue size parameter which dictates the number of messages the Agent's message queue can hold.
             /// If no queue size parameter is given this defaults to 1, meaning that if there is already a message waiting in an Agent's queue then any attempt to send a message to the Agent will have to wait until either the queued message is received, or the send timeout is reached (in which case message sending is considered a failure).
             /// If try_send() is used to send to a full message queue, it will immediately return with failure.
-            #[macro_export]
+            ///
+            /// Alternatively, the queue size can be replaced with `priority_levels: LEVELS, capacity: CAPACITY` to give the Agent a mailbox with a separate queue of the given capacity for each priority level.
+            /// The Agent's inbox then always yields the highest priority message waiting (see `MessageBuilder::with_priority()`).
+            /// Both values must be constants, as the mailbox is statically allocated.
+            ///
+            /// Returns an `AgentRef`, through which the Agent can be sent messages of its own type.
             #[cfg(target_os = "none")]
             macro_rules! _register_agent {
-                ($spawner:ident, $agent_address:ident, $agent:ty, $config:expr, $queue_size: expr) => {{
-                    use post_haste::dependencies::{NoopRawMutex, Channel, task};
+                ($spawner:ident, $agent_address:ident, $agent:ty, $config:expr, priority_levels: $levels:expr, capacity: $capacity:expr) => {{
+                    use post_haste::dependencies::task;
                     use post_haste::agent::Agent;
-                    use crate::postmaster::Message;
+                    use post_haste::mailbox::{Narrowed, PriorityChannel};
+                    use crate::$name::Message;
                     struct Mailbox {
-                        pub inner: Channel<NoopRawMutex, Message, $queue_size>
+                        pub inner: PriorityChannel<Message, { $levels }, { $capacity }>
                     }
 
                     unsafe impl Sync for Mailbox{}
-                    static MAILBOX: Mailbox = Mailbox{ inner: Channel::new()};
+                    static MAILBOX: Mailbox = Mailbox{ inner: PriorityChannel::new()};
 
-                    let agent = <$agent>::create(<$address_enum>::$agent_address, $config).await;
-                    postmaster::set_spawner($spawner);
-                    postmaster::register(<$address_enum>::$agent_address, MAILBOX.inner.sender().into()).await.inspect(|_| {
+                    struct Inbox {
+                        pub inner: Narrowed<Message, <$agent as Agent>::Message>
+                    }
+
+                    unsafe impl Sync for Inbox{}
+                    static INBOX: Inbox = Inbox{ inner: Narrowed::new(&MAILBOX.inner, <<$agent as Agent>::Message>::narrow)};
+
+                    let agent = <$agent as Agent>::create(<$address_enum>::$agent_address, $config).await;
+                    crate::$name::set_spawner($spawner);
+                    crate::$name::register_mailbox(<$address_enum>::$agent_address, MAILBOX.inner.mailbox(), <<$agent as Agent>::Message>::accepts).await.map(|token| {
 
                         #[task]
-                        async fn run_agent(agent: $agent) {
-                            agent.run(MAILBOX.inner.receiver().into()).await
+                        async fn run_agent(agent: $agent, source: crate::$name::ContextSource) {
+                            agent.run(INBOX.inner.inbox().with_latency(crate::$name::latency_histograms(<$address_enum>::$agent_address)).with_source::<$address_enum>(source)).await
                         }
-                        $spawner.spawn(run_agent(agent).unwrap());
+                        $spawner.spawn(run_agent(agent, crate::$name::context_source(token)).unwrap());
+                        crate::$name::AgentRef::<$agent>::new(<$address_enum>::$agent_address)
                     })
                 }};
+                ($spawner:ident, $agent_address:ident, $agent:ty, $config:expr, $queue_size: expr) => {
+                    crate::$name::register_agent!($spawner, $agent_address, $agent, $config, priority_levels: 1, capacity: $queue_size)
+                };
                 ($spawner:ident, $agent_address:ident, $agent:ty, $config:expr) => {
-                    crate::postmaster::register_agent!($spawner, $agent_address, $agent, $config, 1)
+                    crate::$name::register_agent!($spawner, $agent_address, $agent, $config, 1)
                 }
             }
 
             #[doc(hidden)]
-            pub use _register_agent as register_agent;
+            pub(crate) use _register_agent as register_agent;
+
+            /// Initialises a supervised Agent and its message queue.
+            /// This works in the same way as `register_agent!()`, except that the Postmaster keeps watch over the Agent's main loop.
+            /// Should the main loop panic, the Agent is re-created from a clone of the given Config and its main loop is restarted at the same address.
+            /// Messages waiting in the Agent's message queue are kept across the restart, although the message the Agent was handling when it failed is lost.
+            /// Which Agents are restarted, and how often, is configured with `postmaster::set_supervision()`.
+            /// The Agent's Config type must implement `Clone`.
+            /// Returns an `AgentRef`, as `register_agent!()` does.
+            #[cfg(not(target_os = "none"))]
+            macro_rules! _register_supervised_agent {
+                ($agent_address:ident, $agent:ty, $config:expr, $queue_size: expr) => {{
+                    use crate::$name::Message;
+                    use post_haste::agent::Agent;
+                    use post_haste::dependencies::*;
+                    let address = <$address_enum>::$agent_address;
+                    let config = $config;
+                    let (sender, receiver) = channel::<Message>($queue_size);
+
+                    let agent = <$agent as Agent>::create(address, config.clone()).await;
+                    crate::$name::register_mailbox(address, sender.into(), <<$agent as Agent>::Message>::accepts).await.map(|token| {
+                        let idle = std::sync::Arc::new(Notify::new());
+                        let task = tokio::task::spawn(crate::$name::supervise(
+                            crate::$name::context_source(token),
+                            receiver,
+                            idle.clone(),
+                            agent,
+                            <<$agent as Agent>::Message>::narrow,
+                            move || <$agent as Agent>::create(address, config.clone()),
+                            |agent: $agent, inbox| async move {
+                                agent.run(inbox).await;
+                            },
+                        ));
+                        crate::$name::track_agent(address, task, idle);
+                        crate::$name::AgentRef::<$agent>::new(address)
+                    })
+                }};
+                ($agent_address:ident, $agent:ty, $config:expr) => {
+                    crate::$name::register_supervised_agent!($agent_address, $agent, $config, 1)
+                };
+            }
+
+            #[doc(hidden)]
+            #[cfg(not(target_os = "none"))]
+            pub(crate) use _register_supervised_agent as register_supervised_agent;
+
+            #[doc(hidden)]
+            #[cfg(not(target_os = "none"))]
+            pub async fn supervise<A, M, C, CF, R, RF>(
+                source: ContextSource,
+                mailbox: Receiver<Message>,
+                idle: Arc<Notify>,
+                agent: A,
+                narrow: fn(Message) -> Option<M>,
+                create: C,
+                run: R,
+            ) where
+                A: Send + 'static,
+                M: Send + 'static,
+                C: Fn() -> CF,
+                CF: core::future::Future<Output = A>,
+                R: Fn(A, post_haste::agent::Inbox<M>) -> RF,
+                RF: core::future::Future<Output = ()> + Send + 'static,
+            {
+                postmaster_internal::supervise(source, mailbox, idle, agent, narrow, create, run).await
+            }
+
+            #[doc(hidden)]
+            #[cfg(not(target_os = "none"))]
+            pub fn track_agent(address: $address_enum, task: task::JoinHandle<()>, idle: Arc<Notify>) {
+                postmaster_internal::track_agent(address, task, idle)
+            }
+
+            #[doc(hidden)]
+            #[cfg(not(target_os = "none"))]
+            pub fn shutdown_flag() -> &'static AtomicBool {
+                postmaster_internal::shutdown_flag()
+            }
 
             /// This function can be used to register a standalone address with the Postmaster.
             /// When registering an Agent (using the register_agent!() macro), the Agent's message queue is generated and assigned to the given address automatically.
             /// However, there may be some scenarios where you may want to register a message queue without tying it to an Agent.
             /// An example of this would be if you wanted to be able to receive messages on the main task, or providing a debug node for Agents to send diagnostics to.
+            /// On success, the address's `SourceToken` is returned, which is needed to send messages from the address if the Postmaster was initialised with `sources: authenticated`.
             ///
             /// # Example
             /// ```rust
@@ -178,14 +514,16 @@ macro_rules! init_postmaster {
             pub async fn register(
                 address: $address_enum,
                 mailbox: DynamicSender<'static, Message>,
-            ) -> Result<(), PostmasterError> {
-                postmaster_internal::register(address, mailbox).await
+            ) -> Result<SourceToken, PostmasterError> {
+                let generation = postmaster_internal::register(address, mailbox.into(), |_| true).await?;
+                Ok(SourceToken { address, generation })
             }
 
             /// This function can be used to register a standalone address with the Postmaster.
             /// When registering an Agent (using the register_agent!() macro), the Agent's message queue is generated and assigned to the given address automatically.
             /// However, there may be some scenarios where you may want to register a message queue without tying it to an Agent.
             /// An example of this would be if you wanted to be able to receive messages on the main task, or providing a debug node for Agents to send diagnostics to.
+            /// On success, the address's `SourceToken` is returned, which is needed to send messages from the address if the Postmaster was initialised with `sources: authenticated`.
             ///
             /// # Example
             /// ```rust,ignore
@@ -208,8 +546,60 @@ macro_rules! init_postmaster {
             pub async fn register(
                 address: $address_enum,
                 mailbox: Sender<Message>,
-            ) -> Result<(), PostmasterError> {
-                postmaster_internal::register(address, mailbox).await
+            ) -> Result<SourceToken, PostmasterError> {
+                let generation = postmaster_internal::register(address, mailbox.into(), |_| true).await?;
+                Ok(SourceToken { address, generation })
+            }
+
+            #[doc(hidden)]
+            pub fn latency_histograms(address: $address_enum) -> &'static post_haste::latency::LatencyHistograms {
+                postmaster_internal::latency_histograms(address)
+            }
+
+            #[doc(hidden)]
+            pub async fn register_mailbox(
+                address: $address_enum,
+                mailbox: post_haste::mailbox::Mailbox<Message>,
+                accepts: fn(&$payload_enum) -> bool,
+            ) -> Result<SourceToken, PostmasterError> {
+                let generation = postmaster_internal::register(address, mailbox, accepts).await?;
+                Ok(SourceToken { address, generation })
+            }
+
+            /// Remove the mailbox registered at an address.
+            /// Once this returns, any attempt to send a message to the address fails with `PostmasterError::NoRecipient`, and a new mailbox (or Agent) can be registered at the address.
+            /// Messages which were already queued in the mailbox are left where they are.
+            /// On tokio, the receiving end of the mailbox will yield any queued messages and then report that the channel has closed.
+            /// If an Agent was registered at the address, its task is stopped along with any messages left in its inbox, unless it is the Agent which is deregistering the address.
+            /// Fails with `PostmasterError::NoRecipient` if nothing is registered at the address.
+            ///
+            /// The address is given by its owner: the address itself, or, if the Postmaster was initialised with `sources: authenticated`, the `SourceToken` handed out when it was registered.
+            /// Deregistering gives up the token, and any other token for the address becomes stale, so it can no longer be used to send messages.
+            /// Fails with `PostmasterError::StaleSource` if the token is already stale.
+            pub fn deregister(owner: impl Owner) -> Result<(), PostmasterError> {
+                postmaster_internal::deregister(owner.address(), owner.generation())
+            }
+
+            /// Remove the mailbox registered at an address, and drain any messages still queued in it.
+            /// This works in the same way as `postmaster::deregister()`, but also takes the receiving end of the mailbox and returns an iterator over the messages which were queued when the mailbox was deregistered.
+            /// This is most useful for standalone mailboxes created with `postmaster::register()`, or for an Agent retiring itself from its address, as the caller must hold the inbox.
+            /// On tokio, the receiving end of a standalone mailbox can be turned into an `Inbox` with `Inbox::from()`.
+            /// Make sure that the inbox belongs to the given address: the Postmaster has no way to check this.
+            pub fn deregister_and_drain(
+                owner: impl Owner,
+                inbox: &mut post_haste::agent::Inbox<Message>,
+            ) -> Result<impl Iterator<Item = Message> + '_, PostmasterError> {
+                postmaster_internal::deregister(owner.address(), owner.generation())?;
+                #[cfg(not(target_os = "none"))]
+                let drain = core::iter::from_fn(|| inbox.try_recv().ok());
+                #[cfg(target_os = "none")]
+                let drain = core::iter::from_fn(|| inbox.try_receive().ok());
+                Ok(drain)
+            }
+
+            /// Check whether a mailbox is currently registered at an address.
+            pub fn is_registered(address: $address_enum) -> bool {
+                postmaster_internal::registered_addresses()[address.index()].is_some()
             }
 
 
@@ -220,14 +610,13 @@ macro_rules! init_postmaster {
             /// - The message could not be added to the queue before the timeout expired.
             /// Reasons for failure include:
             /// - The message queue being consistently full for longer than the timeout
-            /// - The Postmaster being unable to acquire a lock on the senders before the timeout expires
             /// - There being no recipient registered at the destination address
             pub async fn send(
                 destination: $address_enum,
-                source: $address_enum,
-                payload: $payload_enum,
+                source: impl Source,
+                payload: impl Into<$payload_enum>,
             ) -> Result<(), PostmasterError> {
-                postmaster_internal::send_internal(destination, Message { source, payload }, None)
+                postmaster_internal::send_internal(destination, Message::from_source(source, payload.into()), None)
                     .await
             }
 
@@ -235,14 +624,83 @@ macro_rules! init_postmaster {
             /// This function works very similarly to `postmaster::send()`, however if this is not immediately possible it will return with an error rather than attempting to wait for a timeout period.
             /// Reasons for failure include:
             /// - The recipient's message queue being full
-            /// - The lock on the senders not being available
             /// - There being no recipient registered at the destination address
             pub fn try_send(
                 destination: $address_enum,
-                source: $address_enum,
-                payload: $payload_enum,
+                source: impl Source,
+                payload: impl Into<$payload_enum>,
             ) -> Result<(), PostmasterError> {
-                postmaster_internal::try_send_internal(destination, Message { source, payload })
+                postmaster_internal::try_send_internal(destination, Message::from_source(source, payload.into()))
+            }
+
+            /// Send a request and wait for the recipient to reply
+            /// The message is delivered with a `ReplyHandle` in its `reply_to` field, which the recipient uses to send a payload back to the requester.
+            /// The future returned by this function resolves to the reply payload, meaning that the reply never passes through the requester's own inbox.
+            /// The Postmaster's default timeout applies both to delivering the request and, separately, to waiting for the reply.
+            /// Reasons for failure include:
+            /// - Any of the reasons for which `postmaster::send()` can fail
+            /// - No reply arriving before the timeout expires
+            /// - The recipient dropping the `ReplyHandle` without replying
+            /// - (Embassy only) All of the reply slots being in use by other pending requests
+            pub async fn request(
+                destination: $address_enum,
+                source: impl Source,
+                payload: impl Into<$payload_enum>,
+            ) -> Result<$payload_enum, PostmasterError> {
+                postmaster_internal::request_internal(destination, Message::from_source(source, payload.into()), None)
+                    .await
+            }
+
+            /// Send a copy of a message to every registered address, apart from the source.
+            /// The payload is cloned for each recipient, and each copy is sent in turn using the Postmaster's default timeout.
+            /// A failure to deliver to one recipient does not stop delivery to the others: the outcome for every recipient is collected in the returned `DeliveryReport`.
+            pub async fn broadcast<P: Clone + Into<$payload_enum>>(
+                source: impl Source,
+                payload: P,
+            ) -> DeliveryReport {
+                let generation = source.generation();
+                let source = source.address();
+                let mut report = DeliveryReport::new();
+                for destination in postmaster_internal::registered_addresses().into_iter().flatten() {
+                    if destination.index() != source.index() {
+                        report.record(
+                            destination,
+                            postmaster_internal::send_internal(
+                                destination,
+                                Message { generation, ..Message::new(source, payload.clone().into()) },
+                                None,
+                            )
+                            .await,
+                        );
+                    }
+                }
+                report
+            }
+
+            /// Send a copy of a message to each of the given addresses.
+            /// The payload is cloned for each recipient, and each copy is sent in turn using the Postmaster's default timeout.
+            /// The destinations may be a named group declared in `init_postmaster!()` (see the `groups` module).
+            /// A failure to deliver to one recipient does not stop delivery to the others: the outcome for every recipient is collected in the returned `DeliveryReport`.
+            pub async fn multicast<P: Clone + Into<$payload_enum>>(
+                destinations: &[$address_enum],
+                source: impl Source,
+                payload: P,
+            ) -> DeliveryReport {
+                let generation = source.generation();
+                let source = source.address();
+                let mut report = DeliveryReport::new();
+                for &destination in destinations {
+                    report.record(
+                        destination,
+                        postmaster_internal::send_internal(
+                            destination,
+                            Message { generation, ..Message::new(source, payload.clone().into()) },
+                            None,
+                        )
+                        .await,
+                    );
+                }
+                report
             }
 
             /// Begin building a message with custom settings
@@ -250,17 +708,85 @@ macro_rules! init_postmaster {
             /// The MessageBuilder provides methods to further configure the message before it is sent.
             pub fn message(
                 destination: $address_enum,
-                source: $address_enum,
-                payload: $payload_enum,
+                source: impl Source,
+                payload: impl Into<$payload_enum>,
             ) -> MessageBuilder {
                 MessageBuilder {
                     destination,
-                    message: Message { source, payload },
+                    message: Message::from_source(source, payload.into()),
                     timeout: None,
-                    delay: None,
                 }
             }
 
+            /// Begin building a tap, through which every message sent through the Postmaster can be observed without involving the Agents concerned.
+            /// The returned TapBuilder chooses which messages to observe, and whether a record of each is forwarded to a mailbox or passed to a callback.
+            /// Each record gives the source, destination, payload variant and outcome of the send, along with when it was made.
+            ///
+            /// # Example
+            /// ```rust,ignore
+            /// // With a `Tap(postmaster::TapRecord)` variant in the payload enum...
+            /// postmaster::register_agent!(Debug, postmaster::TapPrinter, |message| match message.payload {
+            ///     Payloads::Tap(record) => Some(record),
+            ///     _ => None,
+            /// }, 16)
+            /// .unwrap();
+            /// postmaster::tap().destination(Address::Sequencer).forward_to(Address::Debug, Payloads::Tap);
+            /// ```
+            pub fn tap() -> TapBuilder {
+                TapBuilder {
+                    sources: None,
+                    destinations: None,
+                    describe: None,
+                }
+            }
+
+            /// Remove the tap, if one is installed.
+            pub fn remove_tap() {
+                postmaster_internal::remove_tap()
+            }
+
+            /// Start recording every message sent through the Postmaster, so that the sequence of messages can be replayed later (e.g. on a desktop build, with `postmaster::replay()`).
+            /// The Recorder writes the recording into its sink as it goes: first a header, then an entry for each message giving when it was sent, its source, destination and payload.
+            /// Creating the Recorder requires the address and payload enums to implement `post_haste::record::Record`, which encodes them.
+            /// Messages are recorded when they are sent, whether or not they are then delivered.
+            /// Replaces the recording which is currently running, if there is one.
+            ///
+            /// # Example
+            /// ```rust,ignore
+            /// static RECORDING: std::sync::Mutex<Vec<u8>> = std::sync::Mutex::new(Vec::new());
+            ///
+            /// postmaster::start_recording(post_haste::record::Recorder::new(&RECORDING));
+            /// ```
+            pub fn start_recording(recorder: post_haste::record::Recorder<$address_enum, $payload_enum>) {
+                postmaster_internal::start_recording(recorder)
+            }
+
+            /// Stop the recording which is currently running, if there is one.
+            pub fn stop_recording() {
+                postmaster_internal::stop_recording()
+            }
+
+            /// Re-send the messages read back from a recording, from their original sources to their original destinations.
+            /// The messages are sent one at a time using the Postmaster's default timeout, with the spacing given by the timing.
+            /// Messages which can't be delivered are counted in the returned summary, and replay carries on with the next message.
+            /// Fails with `PostmasterError::InvalidReplayTiming`, without sending anything, if the timing is accelerated by a factor which is not a finite number greater than zero.
+            ///
+            /// # Example
+            /// ```rust,ignore
+            /// use post_haste::record::{LogReader, ReplayTiming};
+            ///
+            /// let recording = std::fs::read("field-unit.log").unwrap();
+            /// let messages = LogReader::new(&recording).unwrap().map_while(Result::ok);
+            /// let summary = postmaster::replay(messages, ReplayTiming::Accelerated(10.0)).await.unwrap();
+            /// ```
+            #[cfg(not(target_os = "none"))]
+            pub async fn replay(
+                messages: impl IntoIterator<Item = RecordedMessage>,
+                timing: post_haste::record::ReplayTiming,
+            ) -> Result<post_haste::record::ReplaySummary, PostmasterError> {
+                postmaster_internal::replay(messages, timing).await
+            }
+
             /// Retrieve diagnostic information for the Postmaster
             /// The diagnostics contain information about how many messages have been sent in total since boot, and how many (if any) sending failures have occurred.
             /// This provides a simple high-level overview of the health of the system.
@@ -268,11 +794,94 @@ macro_rules! init_postmaster {
                 postmaster_internal::get_diagnostics()
             }
 
+            /// Retrieve diagnostic information for a single address.
+            /// This shows whether anything is registered at the address, how many messages have been delivered to it, and how many attempts to send to it have failed (and why).
+            /// It also shows how many messages are currently waiting in the address's mailbox, and the most there have been at any one time.
+            pub fn get_address_diagnostics(address: $address_enum) -> AddressDiagnostics {
+                postmaster_internal::get_address_diagnostics(address)
+            }
+
+            /// Retrieve diagnostic information for every address, in the order in which the addresses are declared.
+            pub fn address_diagnostics() -> impl Iterator<Item = ($address_enum, AddressDiagnostics)> {
+                (0..ADDRESS_COUNT)
+                    .filter_map(<$address_enum>::from_index)
+                    .map(|address| (address, postmaster_internal::get_address_diagnostics(address)))
+            }
+
             /// Change the Postmaster's default timeout for sending messages
             pub fn set_timeout(timeout_us: u32) {
                 postmaster_internal::set_timeout(timeout_us)
             }
 
+            /// Shut the system down gracefully.
+            /// From the moment this is called, the Postmaster stops accepting messages and registrations, which fail with `PostmasterError::ShuttingDown`.
+            /// Every mailbox is then closed, so each Agent receives the messages left in its inbox followed by `Delivery::Shutdown` (see `Inbox::next()`), which is its notification to tidy up.
+            /// A `MessageHandler` has its `on_shutdown()` hook called at this point.
+            /// Once an Agent has tidied up and gone back to waiting on its inbox, or if its task has already ended, its task is stopped and joined.
+            /// Agents which receive with `Inbox::recv()` are stopped as soon as their inbox has been emptied.
+            /// Fails with `PostmasterError::Timeout` if any Agent had not finished before the timeout expired, although every Agent task will still have been stopped.
+            #[cfg(not(target_os = "none"))]
+            pub async fn shutdown(timeout: Duration) -> Result<(), PostmasterError> {
+                postmaster_internal::shutdown(timeout).await
+            }
+
+            /// Return the Postmaster to the state it was in when the program started.
+            /// Every mailbox is deregistered and every Agent task is stopped (without being notified of a shutdown).
+            /// The diagnostics, subscriptions, tap, recording, supervision settings and default timeout are all reset, and the Postmaster accepts messages again after a shutdown.
+            /// Delayed and periodic messages which are already on their way are not cancelled.
+            /// This is intended for tests, which otherwise share the Postmaster's state with every other test in the same binary (see `postmaster::isolate()`).
+            #[cfg(not(target_os = "none"))]
+            pub fn reset() {
+                postmaster_internal::reset();
+                clear_subscriptions();
+            }
+
+            /// Take sole use of the Postmaster for the duration of a test.
+            /// Tests in the same binary run in parallel by default, but share the one Postmaster, so one test's registrations would otherwise collide with another's.
+            /// This waits until no other test holds an `Isolation`, then resets the Postmaster (see `postmaster::reset()`).
+            /// The Postmaster is reset again when the returned `Isolation` is dropped, even if the test panics.
+            /// Creating a `harness::Harness` does this automatically.
+            /// See the documentation of `post_haste::init_postmaster!()` for an example.
+            #[cfg(not(target_os = "none"))]
+            pub fn isolate() -> Isolation {
+                static ISOLATION: std::sync::Mutex<()> = std::sync::Mutex::new(());
+                let guard = ISOLATION.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+                reset();
+                Isolation { _guard: guard }
+            }
+
+            /// Sole use of the Postmaster, as returned by `postmaster::isolate()`.
+            /// The Postmaster is reset when this is dropped.
+            #[cfg(not(target_os = "none"))]
+            pub struct Isolation {
+                _guard: std::sync::MutexGuard<'static, ()>,
+            }
+
+            #[cfg(not(target_os = "none"))]
+            impl Drop for Isolation {
+                fn drop(&mut self) {
+                    reset();
+                }
+            }
+
+            /// Configure how Agents registered with `register_supervised_agent!()` are restarted.
+            /// The strategy determines whether only the failed Agent or every supervised Agent is restarted, and the limit determines how many restarts are allowed within a window of time before the Postmaster gives up on an Agent.
+            /// By default only the failed Agent is restarted, up to three times within five seconds.
+            #[cfg(not(target_os = "none"))]
+            pub fn set_supervision(strategy: RestartStrategy, limit: RestartLimit) {
+                postmaster_internal::set_supervision(strategy, limit)
+            }
+
+            /// Have a `SupervisionEvent` delivered to the given address whenever a supervised Agent is restarted or given up on.
+            /// The event is turned into a payload using the given function, and is sent from the address of the Agent it concerns.
+            #[cfg(not(target_os = "none"))]
+            pub fn report_supervision_events(
+                address: $address_enum,
+                into_payload: fn(SupervisionEvent<$address_enum>) -> $payload_enum,
+            ) {
+                postmaster_internal::report_supervision_events(address, into_payload)
+            }
+
             /// Pass a reference to the spawner to the Postmaster for use in delayed messages.
             /// Please note that you should not need to call this function, as the Postmaster automatically acquires a reference to the spawner when an Agent is registered with `register_agent!()`.
             #[cfg(target_os = "none")]
@@ -280,6 +889,105 @@ macro_rules! init_postmaster {
                 postmaster_internal::set_spawner(spawner)
             }
 
+            impl<A> AgentRef<A> {
+                /// Create a handle for the Agent registered at an address.
+                /// Prefer the handle returned by `register_agent!()`, which is guaranteed to match the Agent at the address.
+                /// Should a different Agent be registered at the address, sends through the handle fail with `PostmasterError::UnsupportedPayload` unless that Agent also receives the payload.
+                pub fn new(address: $address_enum) -> Self {
+                    Self {
+                        address,
+                        _agent: core::marker::PhantomData,
+                    }
+                }
+
+                /// The address of the Agent, for use with the untyped sending functions (e.g. `postmaster::send()`).
+                pub fn address(&self) -> $address_enum {
+                    self.address
+                }
+            }
+
+            impl<A, P> AgentRef<A>
+            where
+                A: post_haste::agent::Agent<Message = Message<P>>,
+                P: post_haste::PayloadVariant<$payload_enum>,
+            {
+                /// Send the Agent a message using the Postmaster's default timeout (see `postmaster::send()`).
+                pub async fn send(&self, source: impl Source, payload: impl Into<P>) -> Result<(), PostmasterError> {
+                    send(self.address, source, payload.into().into()).await
+                }
+
+                /// Attempt to send the Agent a message without waiting (see `postmaster::try_send()`).
+                pub fn try_send(&self, source: impl Source, payload: impl Into<P>) -> Result<(), PostmasterError> {
+                    try_send(self.address, source, payload.into().into())
+                }
+
+                /// Send the Agent a request and wait for it to reply (see `postmaster::request()`).
+                pub async fn request(&self, source: impl Source, payload: impl Into<P>) -> Result<$payload_enum, PostmasterError> {
+                    request(self.address, source, payload.into().into()).await
+                }
+
+                /// Begin building a message to the Agent with custom settings (see `postmaster::message()`).
+                pub fn message(&self, source: impl Source, payload: impl Into<P>) -> MessageBuilder {
+                    message(self.address, source, payload.into().into())
+                }
+            }
+
+            /// An Agent's view of the Postmaster, which sends messages with the Agent's own address as their source (see `post_haste::handler::Context`).
+            pub type AgentContext = post_haste::handler::Context<$address_enum>;
+
+            impl post_haste::handler::Postmaster for $address_enum {
+                type Payload = $payload_enum;
+                type Timer = DelayedMessageHandle;
+                type Source = ContextSource;
+
+                fn authorise(_authority: post_haste::handler::Authority<Self>, address: Self, generation: u32) -> ContextSource {
+                    authorise(address, generation)
+                }
+
+                fn source_address(source: &ContextSource) -> Self {
+                    source_address(source)
+                }
+
+                fn source_generation(source: &ContextSource) -> u32 {
+                    source_generation(source)
+                }
+
+                async fn send(destination: Self, source: &ContextSource, payload: $payload_enum) -> Result<(), PostmasterError> {
+                    send(destination, source, payload).await
+                }
+
+                async fn send_after(
+                    destination: Self,
+                    source: &ContextSource,
+                    payload: $payload_enum,
+                    delay: Duration,
+                ) -> Result<DelayedMessageHandle, PostmasterError> {
+                    message(destination, source, payload).with_delay(delay).send().await
+                }
+
+                fn cancel(timer: &DelayedMessageHandle) -> Result<(), PostmasterError> {
+                    timer.cancel()
+                }
+
+                fn has_fired(timer: &DelayedMessageHandle) -> bool {
+                    timer.has_fired()
+                }
+            }
+
+            impl<A> Clone for AgentRef<A> {
+                fn clone(&self) -> Self {
+                    *self
+                }
+            }
+
+            impl<A> Copy for AgentRef<A> {}
+
+            impl<A> core::fmt::Debug for AgentRef<A> {
+                fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                    f.debug_tuple("AgentRef").field(&format_args!("{}", self.address.name())).finish()
+                }
+            }
+
             impl MessageBuilder {
                 /// Add a custom timeout to the message.
                 /// When the message is sent, it will use this timeout to determine how long to wait before giving up, rather than the Postmaster's default timeout.
@@ -288,52 +996,380 @@ macro_rules! init_postmaster {
                     self
                 }
 
+                /// Set the priority of the message.
+                /// If the recipient's mailbox has several priority levels, the message will be received ahead of any waiting messages with a lower priority.
+                /// Messages are sent with `Priority::NORMAL` unless this is used.
+                pub fn with_priority(mut self, priority: Priority) -> Self {
+                    self.message.priority = priority;
+                    self
+                }
+
                 /// Add a delay to the message.
                 /// The message is sent immediately, but the Postmaster will not attempt to push the message onto the recipient's queue until the delay has elapsed.
-                /// **Please note** that if a delay is added to the message, but after the delay has elapsed the Postmaster is unable to deliver the message, there is no way for the Postmaster to relay this failure back to the sender.
-                pub fn with_delay(mut self, delay: Duration) -> Self {
-                    self.delay.replace(delay);
-                    self
+                /// This turns the builder into a `DelayedMessageBuilder`, whose `send()` returns a handle which can be used to find out whether the message was eventually delivered.
+                pub fn with_delay(self, delay: Duration) -> DelayedMessageBuilder {
+                    DelayedMessageBuilder {
+                        destination: self.destination,
+                        message: self.message,
+                        timeout: self.timeout,
+                        delay,
+                        failure_notice: None,
+                    }
+                }
+
+                /// Deliver the message repeatedly at a fixed rate.
+                /// As the Postmaster needs a fresh payload for every tick, the payload given to the builder is kept as a template and `replicate` is called on it to produce each delivered payload.
+                /// For payloads which implement `Clone`, `Payloads::clone` can be passed here.
+                /// A function pointer is used rather than a closure or a `Clone` bound because the builder is handed to a statically allocated task on Embassy, which has no allocator to hold a closure, and the payload enum is not required to implement `Clone`.
+                /// The first tick is delivered one period after the message is sent.
+                /// Ticks are scheduled relative to when the message was sent rather than to when the previous tick was delivered, so the schedule does not drift.
+                /// This turns the builder into a `PeriodicMessageBuilder`, whose `send()` returns a handle which can be used to stop the message.
+                /// Periods are measured in whole microseconds, and `send()` fails with `PostmasterError::ZeroPeriod` for any period shorter than one.
+                pub fn with_period(
+                    self,
+                    period: Duration,
+                    replicate: fn(&$payload_enum) -> $payload_enum,
+                ) -> PeriodicMessageBuilder {
+                    PeriodicMessageBuilder {
+                        destination: self.destination,
+                        message: self.message,
+                        timeout: self.timeout,
+                        period,
+                        replicate,
+                        missed_tick_behaviour: MissedTickBehaviour::default(),
+                    }
                 }
 
                 /// Send the configured message.
                 /// This function works in exactly the same way as `postmaster::send()`, except that the timeout scenario may be different depending on whether the timeout for the message was customised.
-                /// If a delay was set, the message will "send" immediately (meaning that the sender can continue executing), but the message won't be delivered until _at least_ the delay has elapsed.
                 /// This function can fail for the following reasons:
                 /// - The message queue being consistently full for longer than the timeout
-                /// - The Postmaster being unable to acquire a lock on the senders before the timeout expires
                 /// - There being no recipient registered at the destination address
-                /// - If a delay was set, the Postmaster was unable to spawn a task to handle sending the message after the delay
                 pub async fn send(self) -> Result<(), PostmasterError> {
-                    match self.delay {
-                        Some(delay) => {
-                            postmaster_internal::spawn_delayed_send(
-                                self.destination,
-                                self.message,
-                                delay,
-                                self.timeout,
-                            )
-                            .await
-                        }
-                        None => {
-                            postmaster_internal::send_internal(
-                                self.destination,
-                                self.message,
-                                self.timeout,
-                            )
-                            .await
-                        }
+                    postmaster_internal::send_internal(self.destination, self.message, self.timeout)
+                        .await
+                }
+
+                /// Send the configured message as a request and wait for the recipient to reply.
+                /// This function works in the same way as `postmaster::request()`, except that the custom timeout (if set) is used for both delivering the request and waiting for the reply.
+                pub async fn expect_reply(self) -> Result<$payload_enum, PostmasterError> {
+                    postmaster_internal::request_internal(self.destination, self.message, self.timeout)
+                        .await
+                }
+            }
+
+            impl DelayedMessageBuilder {
+                /// Add a custom timeout to the message.
+                /// Once the delay has elapsed, the Postmaster will use this timeout to determine how long to wait before giving up on delivery, rather than the Postmaster's default timeout.
+                pub fn with_timeout(mut self, timeout: Duration) -> Self {
+                    self.timeout.replace(timeout);
+                    self
+                }
+
+                /// Provide a payload to be delivered back to the message's source if the delayed message cannot be delivered.
+                /// The notice is sent with the failed message's destination as its source address, so that the original sender can tell which delivery failed.
+                /// This is useful for Agents which use delayed messages as timers, as they would otherwise wait forever for a message which is never going to arrive.
+                pub fn with_failure_notice(mut self, failure_notice: $payload_enum) -> Self {
+                    self.failure_notice.replace(failure_notice);
+                    self
+                }
+
+                /// Send the configured message.
+                /// The message will "send" immediately (meaning that the sender can continue executing), but the message won't be delivered until _at least_ the delay has elapsed.
+                /// On success, a `DelayedMessageHandle` is returned which can be used to await the final outcome of the delivery.
+                /// The handle can simply be dropped if the outcome is not of interest.
+                /// This function can fail if the Postmaster was unable to spawn a task to handle sending the message after the delay.
+                pub async fn send(self) -> Result<DelayedMessageHandle, PostmasterError> {
+                    postmaster_internal::spawn_delayed_send(
+                        self.destination,
+                        self.message,
+                        self.delay,
+                        self.timeout,
+                        self.failure_notice,
+                    )
+                    .await
+                }
+            }
+
+            impl DeliveryReport {
+                fn new() -> Self {
+                    Self {
+                        results: [const { None }; ADDRESS_COUNT],
+                    }
+                }
+
+                fn record(&mut self, destination: $address_enum, result: Result<(), PostmasterError>) {
+                    self.results[destination.index()].replace((destination, result));
+                }
+
+                /// Get the outcome of delivery to the given address, or `None` if the message was not sent to that address.
+                pub fn result(&self, destination: $address_enum) -> Option<&Result<(), PostmasterError>> {
+                    self.results[destination.index()]
+                        .as_ref()
+                        .map(|(_, result)| result)
+                }
+
+                /// Iterate over every address the message was sent to, along with the outcome of delivery to that address.
+                pub fn iter(&self) -> impl Iterator<Item = ($address_enum, &Result<(), PostmasterError>)> {
+                    self.results
+                        .iter()
+                        .flatten()
+                        .map(|(destination, result)| (*destination, result))
+                }
+
+                /// The number of recipients the message was successfully delivered to.
+                pub fn delivered(&self) -> usize {
+                    self.iter().filter(|(_, result)| result.is_ok()).count()
+                }
+
+                /// The number of recipients the message could not be delivered to.
+                pub fn failed(&self) -> usize {
+                    self.iter().filter(|(_, result)| result.is_err()).count()
+                }
+
+                /// Check whether the message was delivered to every recipient.
+                pub fn is_success(&self) -> bool {
+                    self.failed() == 0
+                }
+            }
+
+            impl TapBuilder {
+                /// Only observe messages sent from the given address.
+                /// Can be called several times to observe messages from several addresses; otherwise messages from every address are observed.
+                pub fn source(mut self, source: $address_enum) -> Self {
+                    self.sources.get_or_insert([false; ADDRESS_COUNT])[source.index()] = true;
+                    self
+                }
+
+                /// Only observe messages sent to the given address.
+                /// Can be called several times to observe messages to several addresses; otherwise messages to every address are observed.
+                pub fn destination(mut self, destination: $address_enum) -> Self {
+                    self.destinations.get_or_insert([false; ADDRESS_COUNT])[destination.index()] = true;
+                    self
+                }
+
+                /// Describe each observed payload with the given function, e.g. by naming its variant.
+                /// The description is placed in the record's `description` field.
+                pub fn describe_payload(mut self, describe: fn(&$payload_enum) -> &'static str) -> Self {
+                    self.describe.replace(describe);
+                    self
+                }
+
+                /// Install the tap, forwarding a record of each observed message to the mailbox registered at the given address.
+                /// The record is converted into a payload using the given function.
+                /// Records are forwarded in the same way as `try_send()`, so a tap which falls behind never holds up the rest of the system: records which don't fit in its mailbox are dropped instead.
+                /// Replaces the tap which is currently installed, if there is one.
+                pub fn forward_to(self, address: $address_enum, into_payload: fn(TapRecord) -> $payload_enum) {
+                    postmaster_internal::install_tap(self, postmaster_internal::TapSink::Forward(address, into_payload))
+                }
+
+                /// Install the tap, passing a record of each observed message to the given function.
+                /// The function is called on the sender's task once each send has finished, so it should be quick.
+                /// Replaces the tap which is currently installed, if there is one.
+                pub fn call(self, callback: fn(&TapRecord)) {
+                    postmaster_internal::install_tap(self, postmaster_internal::TapSink::Call(callback))
+                }
+            }
+
+            impl PeriodicMessageBuilder {
+                /// Add a custom timeout to the message.
+                /// The Postmaster will use this timeout to determine how long to wait before giving up on delivering each tick, rather than the Postmaster's default timeout.
+                pub fn with_timeout(mut self, timeout: Duration) -> Self {
+                    self.timeout.replace(timeout);
+                    self
+                }
+
+                /// Choose what happens when the Postmaster falls behind the schedule (the default is `MissedTickBehaviour::Skip`).
+                pub fn on_missed_tick(mut self, missed_tick_behaviour: MissedTickBehaviour) -> Self {
+                    self.missed_tick_behaviour = missed_tick_behaviour;
+                    self
+                }
+
+                /// Start sending the message periodically.
+                /// The message will keep being delivered until `stop()` is called on the returned `PeriodicMessageHandle`.
+                /// Dropping the handle does **not** stop the message.
+                /// Delivery failures for individual ticks are counted in the diagnostics, but do not stop the message.
+                /// This function can fail if the period is zero, or if the Postmaster was unable to spawn a task to handle sending the message.
+                pub async fn send(self) -> Result<PeriodicMessageHandle, PostmasterError> {
+                    if self.period.as_micros() == 0 {
+                        return Err(PostmasterError::ZeroPeriod);
+                    }
+                    postmaster_internal::spawn_periodic_send(self)
+                }
+            }
+
+            impl PeriodicMessageHandle {
+                /// Stop sending the periodic message.
+                /// Any tick which is already being delivered may still arrive.
+                pub fn stop(&self) {
+                    postmaster_internal::stop_periodic(self)
+                }
+            }
+
+            impl DelayedMessageHandle {
+                /// Wait for the delayed message to be delivered, returning the result of the delivery attempt.
+                /// This resolves once the delay has elapsed and the Postmaster has either delivered the message or given up on it.
+                pub async fn outcome(self) -> Result<(), PostmasterError> {
+                    postmaster_internal::delayed_outcome(self).await
+                }
+
+                /// Cancel the delayed message, so that it is never delivered.
+                /// Fails with `PostmasterError::AlreadyFired` if the delay has already elapsed, in which case the message has been (or is being) delivered.
+                pub fn cancel(&self) -> Result<(), PostmasterError> {
+                    postmaster_internal::cancel_delayed(self)
+                }
+
+                /// Replace the remaining delay of the message with a new delay, measured from now.
+                /// This can be used both to postpone and to bring forward the delivery of the message.
+                /// Fails with `PostmasterError::AlreadyFired` if the original delay has already elapsed, or with `PostmasterError::Cancelled` if the message was cancelled.
+                pub fn reschedule(&self, delay: Duration) -> Result<(), PostmasterError> {
+                    postmaster_internal::reschedule_delayed(self, delay)
+                }
+
+                /// Check whether the delay has elapsed and the Postmaster has attempted to deliver the message.
+                pub fn has_fired(&self) -> bool {
+                    postmaster_internal::delayed_has_fired(self)
+                }
+            }
+
+            #[cfg(target_os = "none")]
+            impl Drop for DelayedMessageHandle {
+                fn drop(&mut self) {
+                    postmaster_internal::release_delayed_slot(self.slot, postmaster_internal::HANDLE_HELD)
+                }
+            }
+
+            impl ReplyHandle {
+                /// Send a reply back to the requester.
+                /// The reply is handed directly to the waiting requester rather than being posted to its inbox, so this function does not wait.
+                /// It fails with `PostmasterError::RequesterGone` if the requester is no longer waiting, most likely because its timeout expired.
+                pub fn reply(self, payload: impl Into<$payload_enum>) -> Result<(), PostmasterError> {
+                    postmaster_internal::send_reply(self, payload.into())
+                }
+            }
+
+            #[cfg(target_os = "none")]
+            impl Drop for ReplyHandle {
+                fn drop(&mut self) {
+                    if !self.replied {
+                        postmaster_internal::abandon_reply(self.slot, self.generation)
                     }
                 }
             }
 
             /// The structure of a message in the system.
             /// This structure is automatically generated by the sending functions from the source address and the payload
-            pub struct Message {
+            ///
+            /// An Agent which only receives some of the payloads can declare `Message<T>` as its Message, where `T` is carried by one of the variants of the payload enum (see `#[derive(PostHastePayload)]`).
+            /// Its inbox then yields messages whose payload is already a `T`, and sending it any other payload fails with `PostmasterError::UnsupportedPayload`.
+            pub struct Message<P = $payload_enum> {
                 /// The address from which the message originated
                 pub source: $address_enum,
                 /// The message contents
-                pub payload: $payload_enum,
+                pub payload: P,
+                /// If the message was sent as a request, the handle with which to reply to it
+                pub reply_to: Option<ReplyHandle>,
+                /// The priority with which the message was sent
+                pub priority: Priority,
+                /// When the message was pushed into the destination's mailbox.
+                /// Only recorded when the `latency-histograms` feature of post-haste is enabled.
+                pub enqueued_at: post_haste::latency::Timestamp,
+                /// The span in which the message was sent.
+                /// Handle the message within it (see `TraceContext::instrument()`) to keep any messages sent in response in the same trace.
+                /// Only recorded when the `tracing` feature of post-haste is enabled.
+                pub trace: post_haste::trace::TraceContext,
+                /// The registration of the source which the message was sent from, if it was sent with a `SourceToken`.
+                /// The message is only delivered while that registration is current.
+                generation: Option<u32>,
+            }
+
+            impl Message {
+                fn new(source: $address_enum, payload: $payload_enum) -> Self {
+                    Self {
+                        source,
+                        payload,
+                        reply_to: None,
+                        priority: Priority::NORMAL,
+                        enqueued_at: post_haste::latency::Timestamp::default(),
+                        trace: post_haste::trace::TraceContext::default(),
+                        generation: None,
+                    }
+                }
+
+                fn from_source(source: impl Source, payload: $payload_enum) -> Self {
+                    Self {
+                        generation: source.generation(),
+                        ..Self::new(source.address(), payload)
+                    }
+                }
+            }
+
+            impl<P: post_haste::PayloadVariant<$payload_enum>> Message<P> {
+                #[doc(hidden)]
+                pub fn narrow(message: Message) -> Option<Self> {
+                    post_haste::payload::Narrow::narrow(message).ok()
+                }
+
+                #[doc(hidden)]
+                pub fn accepts(payload: &$payload_enum) -> bool {
+                    P::matches(payload)
+                }
+            }
+
+            impl<P, Q: post_haste::PayloadVariant<P>> post_haste::payload::Narrow<Message<Q>> for Message<P> {
+                fn narrow(self) -> Result<Message<Q>, Self> {
+                    let Message { source, payload, reply_to, priority, enqueued_at, trace, generation } = self;
+                    match Q::narrow(payload) {
+                        Ok(payload) => Ok(Message { source, payload, reply_to, priority, enqueued_at, trace, generation }),
+                        Err(payload) => Err(Message { source, payload, reply_to, priority, enqueued_at, trace, generation }),
+                    }
+                }
+            }
+
+            impl<P> post_haste::handler::Envelope<$address_enum> for Message<P> {
+                fn source(&self) -> $address_enum {
+                    self.source
+                }
+            }
+
+            impl<P> post_haste::latency::Timestamped for Message<P> {
+                fn enqueued_at(&self) -> post_haste::latency::Timestamp {
+                    self.enqueued_at
+                }
+            }
+
+            impl<P> post_haste::trace::Traced for Message<P> {
+                fn trace(&self) -> &post_haste::trace::TraceContext {
+                    &self.trace
+                }
+            }
+
+            /// A handle for replying to a request.
+            /// Messages sent with `postmaster::request()` or `MessageBuilder::expect_reply()` carry one of these in their `reply_to` field.
+            /// Only a single reply can be sent, after which the handle is consumed.
+            /// If the handle is dropped without replying, the requester is notified with `PostmasterError::ReplyDropped`.
+            #[cfg(not(target_os = "none"))]
+            pub struct ReplyHandle {
+                sender: oneshot::Sender<$payload_enum>,
+            }
+
+            /// A handle for replying to a request.
+            /// Messages sent with `postmaster::request()` or `MessageBuilder::expect_reply()` carry one of these in their `reply_to` field.
+            /// Only a single reply can be sent, after which the handle is consumed.
+            /// If the handle is dropped without replying, the requester is notified with `PostmasterError::ReplyDropped`.
+            #[cfg(target_os = "none")]
+            pub struct ReplyHandle {
+                slot: usize,
+                generation: u32,
+                replied: bool,
+            }
+
+            /// A typed handle for sending messages to a registered Agent, returned by `register_agent!()`.
+            /// Its sending functions only accept the Agent's own message type (the `P` of its `Message<P>`), so sending the Agent a payload it can't receive is a compile error rather than a `PostmasterError::UnsupportedPayload`.
+            /// The handle is `Copy`, so it can be handed to any task or Agent which needs to message the Agent.
+            pub struct AgentRef<A> {
+                address: $address_enum,
+                _agent: core::marker::PhantomData<fn() -> A>,
             }
 
             /// A builder for configuring messages.
@@ -342,163 +1378,1209 @@ macro_rules! init_postmaster {
                 destination: $address_enum,
                 message: Message,
                 timeout: Option<Duration>,
-                delay: Option<Duration>,
             }
 
-            /// Contains diagnostic information for the Postmaster.
-            /// Obtained by calling postmaster::get_diagnostics()
-            pub struct Diagnostics {
-                /// The number of messages successfully sent since the Postmaster was initialised.
-                pub messages_sent: usize,
-                /// The number of messages which could not be sent since the Postmaster was initialised.
-                pub send_failures: usize,
+            /// A builder for configuring delayed messages.
+            /// Obtained by calling `with_delay()` on a `MessageBuilder`.
+            pub struct DelayedMessageBuilder {
+                destination: $address_enum,
+                message: Message,
+                timeout: Option<Duration>,
+                delay: Duration,
+                failure_notice: Option<$payload_enum>,
+            }
+
+            /// The outcome of sending a message to several recipients with `postmaster::broadcast()` or `postmaster::multicast()`.
+            pub struct DeliveryReport {
+                results: [Option<($address_enum, Result<(), PostmasterError>)>; ADDRESS_COUNT],
+            }
+
+            /// A builder for configuring the tap.
+            /// Obtained by calling `postmaster::tap()`.
+            pub struct TapBuilder {
+                sources: Option<[bool; ADDRESS_COUNT]>,
+                destinations: Option<[bool; ADDRESS_COUNT]>,
+                describe: Option<fn(&$payload_enum) -> &'static str>,
+            }
+
+            /// A record of a single message observed by the tap (see `postmaster::tap()`).
+            pub type TapRecord = post_haste::tap::TapRecord<$address_enum, $payload_enum>;
+
+            /// A ready-made Agent which prints the records forwarded to it by the tap (see `postmaster::tap()`).
+            /// Requires the address enum to implement `Debug`.
+            #[cfg(not(target_os = "none"))]
+            pub type TapPrinter = post_haste::tap::TapPrinter<$address_enum, $payload_enum, Message>;
+
+            /// A message read back from a recording (see `postmaster::start_recording()`).
+            pub type RecordedMessage = post_haste::record::RecordedMessage<$address_enum, $payload_enum>;
+
+            /// A builder for configuring periodic messages.
+            /// Obtained by calling `with_period()` on a `MessageBuilder`.
+            pub struct PeriodicMessageBuilder {
+                destination: $address_enum,
+                message: Message,
+                timeout: Option<Duration>,
+                period: Duration,
+                replicate: fn(&$payload_enum) -> $payload_enum,
+                missed_tick_behaviour: MissedTickBehaviour,
+            }
+
+            /// A handle to a running periodic message.
+            /// Returned by `PeriodicMessageBuilder::send()`.
+            #[cfg(not(target_os = "none"))]
+            pub struct PeriodicMessageHandle {
+                task: task::JoinHandle<()>,
+            }
+
+            /// A handle to a running periodic message.
+            /// Returned by `PeriodicMessageBuilder::send()`.
+            #[cfg(target_os = "none")]
+            pub struct PeriodicMessageHandle {
+                slot: usize,
+                generation: u32,
+            }
+
+            /// A handle to a delayed message which has been sent but not necessarily delivered yet.
+            /// Returned by `DelayedMessageBuilder::send()`.
+            #[cfg(not(target_os = "none"))]
+            pub struct DelayedMessageHandle {
+                task: task::JoinHandle<Result<(), PostmasterError>>,
+                state: Arc<AtomicU8>,
+                commands: UnboundedSender<postmaster_internal::DelayedCommand>,
+            }
+
+            /// A handle to a delayed message which has been sent but not necessarily delivered yet.
+            /// Returned by `DelayedMessageBuilder::send()`.
+            /// The handle occupies the message's slot in the delayed message pool until both the handle has been dropped and the delivery attempt has finished.
+            #[cfg(target_os = "none")]
+            pub struct DelayedMessageHandle {
+                slot: usize,
+            }
+
+            pub use post_haste::Diagnostics;
+
+            /// Contains diagnostic information for a single address.
+            /// Obtained by calling postmaster::get_address_diagnostics() or postmaster::address_diagnostics()
+            #[derive(Debug, Clone, Copy)]
+            pub struct AddressDiagnostics {
+                /// Whether a mailbox is currently registered at the address.
+                pub registered: bool,
+                /// The number of messages successfully delivered to the address since the Postmaster was initialised.
+                pub delivered: usize,
+                /// The number of messages which could not be delivered to the address, broken down by the error which prevented delivery.
+                pub failures: post_haste::FailureCounts,
+                /// The number of messages currently waiting in the address's mailbox.
+                /// This is `None` if nothing is registered at the address, or (on Embassy) if its mailbox was registered using `postmaster::register()`, as the Postmaster cannot see how full it is.
+                pub queue_depth: Option<usize>,
+                /// The largest number of messages seen waiting in the address's mailbox, measured each time a message is delivered.
+                pub high_water_mark: usize,
+                /// How long messages have waited in the address's mailbox before being received by its Agent.
+                /// Only recorded for Agents registered with `register_agent!()` (or `register_supervised_agent!()`) when the `latency-histograms` feature of post-haste is enabled.
+                pub queueing_latency: post_haste::latency::HistogramSnapshot,
+                /// How long the Agent at the address has spent handling each message, as marked by `Inbox::handled()`.
+                /// Only recorded when the `latency-histograms` feature of post-haste is enabled.
+                pub handling_time: post_haste::latency::HistogramSnapshot,
+            }
+
+            /// Helpers for testing an Agent on its own (tokio only).
+            /// A `Harness` stands in for the rest of the system around the Agent under test.
+            /// It registers mock mailboxes at the addresses the Agent talks to and captures everything sent to them.
+            /// It also injects messages into the Agent and asserts on what the Agent sends back.
+            ///
+            /// Run the test with tokio's clock paused, either with `#[tokio::test(start_paused = true)]` or with `post_haste::harness::SimulatedClock` (behind the `test-harness` feature).
+            /// While the clock is paused, tokio jumps straight to the next timer whenever every task is waiting.
+            /// Delayed messages, send timeouts and the windows given to the assertions are then measured in virtual time.
+            /// A test covering minutes of behaviour runs in moments, and sees the same order of events on every run.
+            /// See the documentation of `post_haste::init_postmaster!()` for an example.
+            #[cfg(not(target_os = "none"))]
+            pub mod harness {
+                use super::{ADDRESS_COUNT, Message, PostmasterError, $address_enum, $payload_enum};
+                use post_haste::PostHasteAddress as _;
+                use post_haste::dependencies::*;
+                use std::collections::VecDeque;
+
+                const MOCK_CAPACITY: usize = 64;
+
+                /// Stands in for the rest of the system around an Agent under test.
+                /// The Harness holds sole use of the Postmaster while it exists (see `postmaster::isolate()`), so the Postmaster starts out empty, and everything registered during the test is removed when the Harness is dropped.
+                pub struct Harness {
+                    mocks: [Option<Mock>; ADDRESS_COUNT],
+                    _isolation: super::Isolation,
+                }
+
+                struct Mock {
+                    receiver: Receiver<Message>,
+                    /// Messages which have arrived in the mailbox but have not yet been claimed by an assertion
+                    captured: VecDeque<Message>,
+                }
+
+                impl Mock {
+                    fn collect(&mut self) {
+                        while let Ok(message) = self.receiver.try_recv() {
+                            self.captured.push_back(message);
+                        }
+                    }
+                }
+
+                impl Harness {
+                    /// Create a Harness, waiting until no other test holds sole use of the Postmaster.
+                    pub fn new() -> Self {
+                        Self {
+                            mocks: [const { None }; ADDRESS_COUNT],
+                            _isolation: super::isolate(),
+                        }
+                    }
+
+                    /// Register a mock mailbox at an address, capturing every message sent to it.
+                    pub async fn mock(&mut self, address: $address_enum) -> Result<(), PostmasterError> {
+                        let (sender, receiver) = channel(MOCK_CAPACITY);
+                        super::register(address, sender).await?;
+                        self.mocks[address.index()] = Some(Mock {
+                            receiver,
+                            captured: VecDeque::new(),
+                        });
+                        Ok(())
+                    }
+
+                    /// Register a mock mailbox at each of the addresses.
+                    pub async fn mock_all(&mut self, addresses: &[$address_enum]) -> Result<(), PostmasterError> {
+                        for &address in addresses {
+                            self.mock(address).await?;
+                        }
+                        Ok(())
+                    }
+
+                    /// Send a message into the system as though it came from the source address (usually one of the mocks).
+                    pub async fn inject(
+                        &self,
+                        destination: $address_enum,
+                        source: $address_enum,
+                        payload: impl Into<$payload_enum>,
+                    ) -> Result<(), PostmasterError> {
+                        super::postmaster_internal::send_internal(destination, super::Message::new(source, payload.into()), None)
+                            .await
+                    }
+
+                    /// Let time pass (virtual time, if the clock is paused), so that the Agent under test can act on what it has been sent.
+                    pub async fn wait(&self, duration: Duration) {
+                        time::sleep(duration).await
+                    }
+
+                    /// Take every message captured at a mocked address which has not yet been claimed by an assertion, in the order in which they arrived.
+                    /// Panics if the address has not been mocked.
+                    pub fn take_captured(&mut self, address: $address_enum) -> Vec<Message> {
+                        let mock = self.mock_at(address);
+                        mock.collect();
+                        mock.captured.drain(..).collect()
+                    }
+
+                    /// Wait for a message matching the predicate to arrive at a mocked address, and return it.
+                    /// Messages which were captured earlier and have not yet been claimed are checked first.
+                    /// Any which don't match are left in place for later assertions.
+                    /// Panics if no matching message has arrived by the end of the window, or if the address has not been mocked.
+                    pub async fn expect_message(
+                        &mut self,
+                        address: $address_enum,
+                        within: Duration,
+                        predicate: impl Fn(&Message) -> bool,
+                    ) -> Message {
+                        let deadline = Instant::now() + within;
+                        let mock = self.mock_at(address);
+                        mock.collect();
+                        let mut checked = 0;
+                        loop {
+                            if let Some(position) = mock.captured.iter().skip(checked).position(&predicate) {
+                                return mock.captured.remove(checked + position).unwrap();
+                            }
+                            checked = mock.captured.len();
+                            match time::timeout_at(deadline, mock.receiver.recv()).await {
+                                Ok(Some(message)) => mock.captured.push_back(message),
+                                Ok(None) | Err(_) => panic!(
+                                    "no matching message arrived at {} within {within:?} ({checked} other messages were captured)",
+                                    address.name()
+                                ),
+                            }
+                        }
+                    }
+
+                    /// Check that no message arrives at a mocked address during the window.
+                    /// Messages which were captured before the window began and have not been claimed count as well.
+                    /// Panics if any message is captured, or if the address has not been mocked.
+                    pub async fn expect_no_message(&mut self, address: $address_enum, within: Duration) {
+                        time::sleep(within).await;
+                        let mock = self.mock_at(address);
+                        mock.collect();
+                        assert!(
+                            mock.captured.is_empty(),
+                            "{} unexpected messages arrived at {}",
+                            mock.captured.len(),
+                            address.name()
+                        );
+                    }
+
+                    fn mock_at(&mut self, address: $address_enum) -> &mut Mock {
+                        self.mocks[address.index()]
+                            .as_mut()
+                            .unwrap_or_else(|| panic!("{} has not been mocked", address.name()))
+                    }
+                }
+
+                impl Default for Harness {
+                    fn default() -> Self {
+                        Self::new()
+                    }
+                }
             }
 
             mod postmaster_internal {
                 use super::{
-                    ADDRESS_COUNT, Message, PostmasterError, $address_enum, $payload_enum,
+                    ADDRESS_COUNT, DelayedMessageHandle, Message, PeriodicMessageBuilder,
+                    PeriodicMessageHandle, PostmasterError, Priority, ReplyHandle, $address_enum,
+                    $payload_enum,
                 };
+                #[cfg(not(target_os = "none"))]
+                use super::{ContextSource, authorise, source_address, source_generation};
                 use core::cell::RefCell;
                 use core::sync::atomic::Ordering;
+                use post_haste::PostHasteAddress as _;
                 use post_haste::dependencies::*;
+                use post_haste::routing::RoutingSlot;
+                #[cfg(not(target_os = "none"))]
+                use post_haste::supervision::{RestartLimit, RestartStrategy, SupervisionEvent};
+                #[cfg(target_os = "none")]
                 #[post_haste::dependencies::env_item]
                 const DELAYED_MESSAGE_POOL_SIZE: usize = 8;
+                #[cfg(target_os = "none")]
+                #[post_haste::dependencies::env_item]
+                const PERIODIC_MESSAGE_POOL_SIZE: usize = 4;
+                #[cfg(target_os = "none")]
+                #[post_haste::dependencies::env_item]
+                const REPLY_POOL_SIZE: usize = 4;
+
+                type Mailbox = post_haste::mailbox::Mailbox<Message>;
+                /// Whether the recipient at an address receives a payload (see `post_haste::PayloadVariant`)
+                type Accepts = fn(&$payload_enum) -> bool;
+
+                /// Returns the generation of the new registration, which the address's `SourceToken` carries.
+                pub(super) async fn register(
+                    address: $address_enum,
+                    mailbox: Mailbox,
+                    accepts: Accepts,
+                ) -> Result<u32, PostmasterError> {
+                    let outcome = (|| {
+                        #[cfg(not(target_os = "none"))]
+                        accepting_messages()?;
+                        POSTMASTER.routes[address.index()]
+                            .insert((address, mailbox, accepts))
+                            .map_err(|_| PostmasterError::AddressAlreadyTaken)?;
+                        Ok(POSTMASTER.generations[address.index()].fetch_add(1, Ordering::SeqCst).wrapping_add(1))
+                    })();
+                    let registration = outcome.map(|_| ());
+                    post_haste::trace::registration(&address, &registration);
+                    post_haste::log::registration(&address, &registration);
+                    outcome
+                }
+
+                /// Deregister an address, on behalf of the owner of the given registration if it was given with a `SourceToken`.
+                pub(super) fn deregister(address: $address_enum, generation: Option<u32>) -> Result<(), PostmasterError> {
+                    let index = address.index();
+                    let outcome = match generation {
+                        // Moving on to the next generation first means that only the current owner can deregister the address
+                        Some(generation) => POSTMASTER.generations[index]
+                            .compare_exchange(generation, generation.wrapping_add(1), Ordering::SeqCst, Ordering::SeqCst)
+                            .map_err(|_| PostmasterError::StaleSource)
+                            .and_then(|_| POSTMASTER.routes[index].take().map(|_| ()).ok_or(PostmasterError::NoRecipient)),
+                        None => close_route(index).then_some(()).ok_or(PostmasterError::NoRecipient),
+                    };
+                    post_haste::trace::deregistration(&address, &outcome);
+                    #[cfg(not(target_os = "none"))]
+                    if outcome.is_ok() {
+                        stop_agent(address);
+                    }
+                    outcome
+                }
+
+                /// Stop tracking the Agent registered at an address, and stop its task unless the Agent is the one deregistering the address.
+                /// Otherwise the task would be left running, detached, once another Agent is tracked at the address.
+                #[cfg(not(target_os = "none"))]
+                fn stop_agent(address: $address_enum) {
+                    let agent = POSTMASTER.agents.lock().unwrap()[address.index()].take();
+                    if let Some(AgentTask { task, .. }) = agent
+                        && task::try_id() != Some(task.id())
+                    {
+                        task.abort();
+                    }
+                }
+
+                /// Empty the route at an address, making any `SourceToken` handed out for it stale.
+                fn close_route(index: usize) -> bool {
+                    let closed = POSTMASTER.routes[index].take().is_some();
+                    if closed {
+                        POSTMASTER.generations[index].fetch_add(1, Ordering::SeqCst);
+                    }
+                    closed
+                }
+
+                /// Reject a message sent with a `SourceToken` which was handed out for an earlier registration of its source address.
+                fn check_source(message: &Message) -> Result<(), PostmasterError> {
+                    match message.generation {
+                        Some(generation) if generation != POSTMASTER.generations[message.source.index()].load(Ordering::SeqCst) => {
+                            Err(PostmasterError::StaleSource)
+                        }
+                        _ => Ok(()),
+                    }
+                }
+
+                pub(super) async fn send_internal(
+                    destination: $address_enum,
+                    mut message: Message,
+                    timeout: Option<Duration>,
+                ) -> Result<(), PostmasterError> {
+                    let timeout = resolve_timeout(timeout);
+                    let source = message.source;
+                    let tap = tap_probe(source, destination, &message.payload);
+                    record_message(source, destination, &message.payload);
+                    let trace = post_haste::trace::send_span(
+                        "send",
+                        &source,
+                        &destination,
+                        &message.payload,
+                        Some(timeout),
+                    );
+                    message.trace = trace.clone();
+                    let outcome = trace.instrument(async {
+                        check_source(&message)?;
+                        #[cfg(not(target_os = "none"))]
+                        accepting_messages()?;
+                        #[cfg(not(target_os = "none"))]
+                        return evaluate_diagnostics(destination, tokio::time::timeout(timeout, async {
+                            match POSTMASTER.routes[destination.index()].get() {
+                                None => Err(PostmasterError::NoRecipient),
+                                Some((_, _, accepts)) if !accepts(&message.payload) => {
+                                    Err(PostmasterError::UnsupportedPayload)
+                                }
+                                Some((_, mailbox, _)) => {
+                                    let priority = message.priority;
+                                    message.enqueued_at = post_haste::latency::Timestamp::now();
+                                    mailbox.send(message, priority).await?;
+                                    note_queue_depth(destination, &mailbox);
+                                    Ok(())
+                                }
+                            }
+                        })
+                        .await
+                        .map_err(|_| PostmasterError::Timeout)
+                        .and_then(|result| result));
+
+                        #[cfg(target_os = "none")]
+                        evaluate_diagnostics(
+                            destination,
+                            async {
+                                match POSTMASTER.routes[destination.index()].get() {
+                                    None => Err(PostmasterError::NoRecipient),
+                                    Some((_, _, accepts)) if !accepts(&message.payload) => {
+                                        Err(PostmasterError::UnsupportedPayload)
+                                    }
+                                    Some((_, mailbox, _)) => {
+                                        let priority = message.priority;
+                                        message.enqueued_at = post_haste::latency::Timestamp::now();
+                                        mailbox.send(message, priority).await;
+                                        note_queue_depth(destination, &mailbox);
+                                        Ok(())
+                                    }
+                                }
+                            }
+                            .with_timeout(timeout)
+                            .await
+                            .unwrap_or(Err(PostmasterError::Timeout)),
+                        )
+                    })
+                    .await;
+                    trace.record_outcome(&outcome);
+                    post_haste::log::send_failure(&source, &destination, &outcome);
+                    if let Some(tap) = tap {
+                        tap.deliver(outcome);
+                    }
+                    outcome
+                }
+
+                pub(super) fn try_send_internal(
+                    destination: $address_enum,
+                    mut message: Message,
+                ) -> Result<(), PostmasterError> {
+                    let source = message.source;
+                    let tap = tap_probe(source, destination, &message.payload);
+                    record_message(source, destination, &message.payload);
+                    let trace = post_haste::trace::send_span(
+                        "try_send",
+                        &source,
+                        &destination,
+                        &message.payload,
+                        None,
+                    );
+                    message.trace = trace.clone();
+                    let outcome = trace.in_scope(|| {
+                        check_source(&message)?;
+                        #[cfg(not(target_os = "none"))]
+                        accepting_messages()?;
+                        evaluate_diagnostics(
+                            destination,
+                            match POSTMASTER.routes[destination.index()].get() {
+                                None => Err(PostmasterError::NoRecipient),
+                                Some((_, _, accepts)) if !accepts(&message.payload) => {
+                                    Err(PostmasterError::UnsupportedPayload)
+                                }
+                                Some((_, mailbox, _)) => {
+                                    let priority = message.priority;
+                                    message.enqueued_at = post_haste::latency::Timestamp::now();
+                                    mailbox
+                                        .try_send(message, priority)
+                                        .map(|_| note_queue_depth(destination, &mailbox))
+                                        .map_err(PostmasterError::from)
+                                }
+                            },
+                        )
+                    });
+                    trace.record_outcome(&outcome);
+                    post_haste::log::send_failure(&source, &destination, &outcome);
+                    if let Some(tap) = tap {
+                        tap.deliver(outcome);
+                    }
+                    outcome
+                }
+
+                pub(super) fn registered_addresses() -> [Option<$address_enum>; ADDRESS_COUNT] {
+                    core::array::from_fn(|index| POSTMASTER.routes[index].get().map(|(address, _, _)| address))
+                }
+
+                pub(super) async fn request_internal(
+                    destination: $address_enum,
+                    mut message: Message,
+                    timeout: Option<Duration>,
+                ) -> Result<$payload_enum, PostmasterError> {
+                    let timeout = resolve_timeout(timeout);
+
+                    #[cfg(not(target_os = "none"))]
+                    {
+                        let (sender, receiver) = oneshot::channel();
+                        message.reply_to.replace(ReplyHandle { sender });
+                        send_internal(destination, message, Some(timeout)).await?;
+                        time::timeout(timeout, receiver)
+                            .await
+                            .map_err(|_| PostmasterError::Timeout)?
+                            .map_err(|_| PostmasterError::ReplyDropped)
+                    }
+
+                    #[cfg(target_os = "none")]
+                    {
+                        let slot = ReplySlotGuard::acquire()?;
+                        let reply_slot = &POSTMASTER.reply_slots[slot.index];
+                        message.reply_to.replace(ReplyHandle {
+                            slot: slot.index,
+                            generation: reply_slot.generation.load(Ordering::Relaxed),
+                            replied: false,
+                        });
+                        send_internal(destination, message, Some(timeout)).await?;
+                        reply_slot
+                            .reply
+                            .wait()
+                            .with_timeout(timeout)
+                            .await?
+                            .ok_or(PostmasterError::ReplyDropped)
+                    }
+                }
+
+                #[cfg(not(target_os = "none"))]
+                pub(super) fn send_reply(
+                    handle: ReplyHandle,
+                    payload: $payload_enum,
+                ) -> Result<(), PostmasterError> {
+                    handle
+                        .sender
+                        .send(payload)
+                        .map_err(|_| PostmasterError::RequesterGone)
+                }
+
+                #[cfg(target_os = "none")]
+                pub(super) fn send_reply(
+                    mut handle: ReplyHandle,
+                    payload: $payload_enum,
+                ) -> Result<(), PostmasterError> {
+                    handle.replied = true;
+                    let reply_slot = &POSTMASTER.reply_slots[handle.slot];
+                    if reply_slot.generation.load(Ordering::Relaxed) == handle.generation {
+                        reply_slot.reply.signal(Some(payload));
+                        Ok(())
+                    } else {
+                        Err(PostmasterError::RequesterGone)
+                    }
+                }
+
+                #[cfg(target_os = "none")]
+                pub(super) fn abandon_reply(slot: usize, generation: u32) {
+                    let reply_slot = &POSTMASTER.reply_slots[slot];
+                    if reply_slot.generation.load(Ordering::Relaxed) == generation {
+                        reply_slot.reply.signal(None);
+                    }
+                }
+
+                #[cfg(not(target_os = "none"))]
+                pub(super) async fn spawn_delayed_send(
+                    destination: $address_enum,
+                    message: Message,
+                    delay: Duration,
+                    timeout: Option<Duration>,
+                    failure_notice: Option<$payload_enum>,
+                ) -> Result<DelayedMessageHandle, PostmasterError> {
+                    let state = Arc::new(AtomicU8::new(PENDING));
+                    let (commands, command_receiver) = unbounded_channel();
+                    let control = DelayedControl {
+                        state: state.clone(),
+                        commands: command_receiver,
+                    };
+                    // The deadline is fixed now rather than when the task first runs, which might not be until after the clock has moved on
+                    let deadline = Instant::now() + delay;
+                    let trace = post_haste::trace::delayed_span(&message.source, &destination, &message.payload, delay);
+                    let task = tokio::spawn(deliver_delayed(
+                        destination,
+                        message,
+                        deadline,
+                        timeout,
+                        failure_notice,
+                        control,
+                        trace,
+                    ));
+                    Ok(DelayedMessageHandle {
+                        task,
+                        state,
+                        commands,
+                    })
+                }
+
+                #[cfg(target_os = "none")]
+                pub(super) async fn spawn_delayed_send(
+                    destination: $address_enum,
+                    message: Message,
+                    delay: Duration,
+                    timeout: Option<Duration>,
+                    failure_notice: Option<$payload_enum>,
+                ) -> Result<DelayedMessageHandle, PostmasterError> {
+                    let Some(spawner) = *POSTMASTER.spawner.borrow() else {
+                        return Err(PostmasterError::SpawnerNotSet);
+                    };
+                    let slot = acquire_delayed_slot()?;
+                    let deadline = Instant::now() + delay;
+                    let trace = post_haste::trace::delayed_span(&message.source, &destination, &message.payload, delay);
+                    match delayed_send(slot, destination, message, deadline, timeout, failure_notice, trace) {
+                        Ok(token) => {
+                            spawner.spawn(token);
+                            Ok(DelayedMessageHandle { slot })
+                        }
+                        Err(error) => {
+                            release_delayed_slot(slot, HANDLE_HELD | TASK_RUNNING);
+                            Err(PostmasterError::from(error))
+                        }
+                    }
+                }
+
+                #[cfg(target_os = "none")]
+                #[task(pool_size = DELAYED_MESSAGE_POOL_SIZE)]
+                async fn delayed_send(
+                    slot: usize,
+                    destination: $address_enum,
+                    message: Message,
+                    deadline: Instant,
+                    timeout: Option<Duration>,
+                    failure_notice: Option<$payload_enum>,
+                    trace: post_haste::trace::TraceContext,
+                ) {
+                    let control = DelayedControl { slot };
+                    let outcome =
+                        deliver_delayed(destination, message, deadline, timeout, failure_notice, control, trace)
+                            .await;
+                    POSTMASTER.delayed_slots[slot].outcome.signal(outcome);
+                    release_delayed_slot(slot, TASK_RUNNING);
+                }
+
+                async fn deliver_delayed(
+                    destination: $address_enum,
+                    message: Message,
+                    deadline: Instant,
+                    timeout: Option<Duration>,
+                    failure_notice: Option<$payload_enum>,
+                    mut control: DelayedControl,
+                    trace: post_haste::trace::TraceContext,
+                ) -> Result<(), PostmasterError> {
+                    trace.instrument(async move {
+                        control.wait(deadline).await?;
+                        post_haste::trace::delayed_fired();
+                        let source = message.source;
+                        let outcome = send_internal(destination, message, timeout).await;
+                        if let Err(error) = &outcome {
+                            POSTMASTER.delayed_send_failures.fetch_add(1, Ordering::Relaxed);
+                            post_haste::log::delayed_send_failure(&source, &destination, error);
+                            if let Some(failure_notice) = failure_notice {
+                                // If the notice can't be delivered either there is nobody left to tell, so this result is discarded
+                                let _ = send_internal(source, Message::new(destination, failure_notice), timeout)
+                                    .await;
+                            }
+                        }
+                        outcome
+                    })
+                    .await
+                }
+
+                #[cfg(not(target_os = "none"))]
+                pub(super) async fn delayed_outcome(
+                    handle: DelayedMessageHandle,
+                ) -> Result<(), PostmasterError> {
+                    match handle.task.await {
+                        Ok(outcome) => outcome,
+                        Err(error) => std::panic::resume_unwind(error.into_panic()),
+                    }
+                }
+
+                #[cfg(target_os = "none")]
+                pub(super) async fn delayed_outcome(
+                    handle: DelayedMessageHandle,
+                ) -> Result<(), PostmasterError> {
+                    POSTMASTER.delayed_slots[handle.slot].outcome.wait().await
+                }
+
+                #[cfg(not(target_os = "none"))]
+                pub(super) fn spawn_periodic_send(
+                    builder: PeriodicMessageBuilder,
+                ) -> Result<PeriodicMessageHandle, PostmasterError> {
+                    // The schedule starts now rather than when the task first runs, which might not be until after the clock has moved on
+                    let start = Instant::now();
+                    let task = tokio::spawn(deliver_periodic(builder, start));
+                    Ok(PeriodicMessageHandle { task })
+                }
+
+                #[cfg(target_os = "none")]
+                pub(super) fn spawn_periodic_send(
+                    builder: PeriodicMessageBuilder,
+                ) -> Result<PeriodicMessageHandle, PostmasterError> {
+                    let Some(spawner) = *POSTMASTER.spawner.borrow() else {
+                        return Err(PostmasterError::SpawnerNotSet);
+                    };
+                    let slot = POSTMASTER
+                        .periodic_slots
+                        .iter()
+                        .position(|slot| {
+                            slot.in_use
+                                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                                .is_ok()
+                        })
+                        .ok_or(PostmasterError::PeriodicMessagePoolFull)?;
+                    let periodic_slot = &POSTMASTER.periodic_slots[slot];
+                    periodic_slot.stop.reset();
+                    let generation = periodic_slot.generation.load(Ordering::Relaxed);
+                    match periodic_send(slot, builder, Instant::now()) {
+                        Ok(token) => {
+                            spawner.spawn(token);
+                            Ok(PeriodicMessageHandle { slot, generation })
+                        }
+                        Err(_) => {
+                            release_periodic_slot(slot);
+                            Err(PostmasterError::PeriodicMessagePoolFull)
+                        }
+                    }
+                }
+
+                #[cfg(target_os = "none")]
+                #[task(pool_size = PERIODIC_MESSAGE_POOL_SIZE)]
+                async fn periodic_send(slot: usize, builder: PeriodicMessageBuilder, start: Instant) {
+                    deliver_periodic(builder, start, slot).await;
+                    release_periodic_slot(slot);
+                }
+
+                async fn deliver_periodic(
+                    builder: PeriodicMessageBuilder,
+                    start: Instant,
+                    #[cfg(target_os = "none")] slot: usize,
+                ) {
+                    let PeriodicMessageBuilder {
+                        destination,
+                        message: Message { source, payload: template, priority, generation, .. },
+                        timeout,
+                        period,
+                        replicate,
+                        missed_tick_behaviour,
+                    } = builder;
+                    let period = period.as_micros() as u64;
+                    let mut scheduled = period;
+                    loop {
+                        let tick = start + Duration::from_micros(scheduled);
+                        #[cfg(not(target_os = "none"))]
+                        time::sleep_until(tick).await;
+                        #[cfg(target_os = "none")]
+                        if POSTMASTER.periodic_slots[slot]
+                            .stop
+                            .wait()
+                            .with_deadline(tick)
+                            .await
+                            .is_ok()
+                        {
+                            return;
+                        }
+
+                        // Failures are recorded in the diagnostics, and the next tick is still attempted unless the Postmaster is shutting down
+                        let message = Message {
+                            priority,
+                            generation,
+                            ..Message::new(source, replicate(&template))
+                        };
+                        match send_internal(destination, message, timeout).await {
+                            // A stale source will never be able to send again
+                            Err(PostmasterError::StaleSource) => return,
+                            #[cfg(not(target_os = "none"))]
+                            Err(PostmasterError::ShuttingDown) => return,
+                            _ => {}
+                        }
+                        let now = Instant::now().duration_since(start).as_micros() as u64;
+                        scheduled = missed_tick_behaviour.next_tick(scheduled, now, period);
+                    }
+                }
+
+                #[cfg(not(target_os = "none"))]
+                pub(super) fn stop_periodic(handle: &PeriodicMessageHandle) {
+                    handle.task.abort();
+                }
+
+                #[cfg(target_os = "none")]
+                pub(super) fn stop_periodic(handle: &PeriodicMessageHandle) {
+                    let periodic_slot = &POSTMASTER.periodic_slots[handle.slot];
+                    if periodic_slot.generation.load(Ordering::Relaxed) == handle.generation {
+                        periodic_slot.stop.signal(());
+                    }
+                }
+
+                #[cfg(target_os = "none")]
+                fn release_periodic_slot(slot: usize) {
+                    let periodic_slot = &POSTMASTER.periodic_slots[slot];
+                    periodic_slot.generation.fetch_add(1, Ordering::Relaxed);
+                    periodic_slot.in_use.store(false, Ordering::Release);
+                }
+
+                pub(super) fn cancel_delayed(handle: &DelayedMessageHandle) -> Result<(), PostmasterError> {
+                    claim_pending(delayed_state(handle), CANCELLED)?;
+                    send_delayed_command(handle, DelayedCommand::Cancel);
+                    Ok(())
+                }
+
+                pub(super) fn reschedule_delayed(
+                    handle: &DelayedMessageHandle,
+                    delay: Duration,
+                ) -> Result<(), PostmasterError> {
+                    // Holding the state at RESCHEDULING stops the task from firing until the new deadline has been handed over
+                    let state = delayed_state(handle);
+                    claim_pending(state, RESCHEDULING)?;
+                    send_delayed_command(handle, DelayedCommand::Reschedule(Instant::now() + delay));
+                    state.store(PENDING, Ordering::Release);
+                    Ok(())
+                }
+
+                /// Move a pending delayed message into the given state, failing if it has already fired or been cancelled.
+                fn claim_pending(state: &AtomicU8, next: u8) -> Result<(), PostmasterError> {
+                    loop {
+                        match state.compare_exchange_weak(PENDING, next, Ordering::AcqRel, Ordering::Acquire) {
+                            Ok(_) => return Ok(()),
+                            // Another reschedule is part way through handing over its deadline, which it does without waiting
+                            Err(RESCHEDULING) => core::hint::spin_loop(),
+                            Err(PENDING) => {}
+                            Err(state) => return Err(state_error(state)),
+                        }
+                    }
+                }
+
+                pub(super) fn delayed_has_fired(handle: &DelayedMessageHandle) -> bool {
+                    delayed_state(handle).load(Ordering::Acquire) == FIRED
+                }
+
+                /// Mark a pending delayed message as fired, returning the state it was in instead if it was not pending.
+                fn fire(state: &AtomicU8) -> Result<(), u8> {
+                    state
+                        .compare_exchange(PENDING, FIRED, Ordering::AcqRel, Ordering::Acquire)
+                        .map(|_| ())
+                }
+
+                fn state_error(state: u8) -> PostmasterError {
+                    if state == CANCELLED {
+                        PostmasterError::Cancelled
+                    } else {
+                        PostmasterError::AlreadyFired
+                    }
+                }
+
+                #[cfg(not(target_os = "none"))]
+                fn delayed_state(handle: &DelayedMessageHandle) -> &AtomicU8 {
+                    &handle.state
+                }
+
+                #[cfg(target_os = "none")]
+                fn delayed_state(handle: &DelayedMessageHandle) -> &AtomicU8 {
+                    &POSTMASTER.delayed_slots[handle.slot].state
+                }
+
+                #[cfg(not(target_os = "none"))]
+                fn send_delayed_command(handle: &DelayedMessageHandle, command: DelayedCommand) {
+                    // The task only stops listening once the message has fired or been cancelled, in which case the command is moot
+                    let _ = handle.commands.send(command);
+                }
+
+                #[cfg(target_os = "none")]
+                fn send_delayed_command(handle: &DelayedMessageHandle, command: DelayedCommand) {
+                    POSTMASTER.delayed_slots[handle.slot].command.signal(command)
+                }
+
+                /// Instructions from a `DelayedMessageHandle` to the task holding the delayed message
+                pub(super) enum DelayedCommand {
+                    Cancel,
+                    Reschedule(Instant),
+                }
+
+                const PENDING: u8 = 0;
+                const FIRED: u8 = 1;
+                const CANCELLED: u8 = 2;
+                const RESCHEDULING: u8 = 3;
+
+                /// The delivery task's side of a `DelayedMessageHandle`
+                #[cfg(not(target_os = "none"))]
+                struct DelayedControl {
+                    state: Arc<AtomicU8>,
+                    commands: UnboundedReceiver<DelayedCommand>,
+                }
+
+                #[cfg(not(target_os = "none"))]
+                impl DelayedControl {
+                    /// Wait for the deadline to pass, following any commands from the handle in the meantime.
+                    /// Returns an error if the message was cancelled.
+                    async fn wait(&mut self, mut deadline: Instant) -> Result<(), PostmasterError> {
+                        loop {
+                            let command = match time::timeout_at(deadline, self.commands.recv()).await {
+                                Ok(Some(command)) => command,
+                                // The handle has been dropped, so nothing can change the deadline any more
+                                Ok(None) => {
+                                    time::sleep_until(deadline).await;
+                                    return fire(&self.state).map_err(state_error);
+                                }
+                                Err(_) => match fire(&self.state) {
+                                    Ok(()) => return Ok(()),
+                                    // The handle is part way through rescheduling, so the new deadline is on its way
+                                    Err(RESCHEDULING) => match self.commands.recv().await {
+                                        Some(command) => command,
+                                        None => continue,
+                                    },
+                                    Err(state) => return Err(state_error(state)),
+                                },
+                            };
+                            match command {
+                                DelayedCommand::Reschedule(new_deadline) => deadline = new_deadline,
+                                DelayedCommand::Cancel => return Err(PostmasterError::Cancelled),
+                            }
+                        }
+                    }
+                }
+
+                /// The delivery task's side of a `DelayedMessageHandle`
+                #[cfg(target_os = "none")]
+                struct DelayedControl {
+                    slot: usize,
+                }
+
+                #[cfg(target_os = "none")]
+                impl DelayedControl {
+                    /// Wait for the deadline to pass, following any commands from the handle in the meantime.
+                    /// Returns an error if the message was cancelled.
+                    async fn wait(&mut self, mut deadline: Instant) -> Result<(), PostmasterError> {
+                        let delayed_slot = &POSTMASTER.delayed_slots[self.slot];
+                        loop {
+                            let command = match delayed_slot.command.wait().with_deadline(deadline).await {
+                                Ok(command) => command,
+                                Err(_) => match fire(&delayed_slot.state) {
+                                    Ok(()) => return Ok(()),
+                                    // The handle is part way through rescheduling, so the new deadline is on its way
+                                    Err(RESCHEDULING) => delayed_slot.command.wait().await,
+                                    Err(state) => return Err(state_error(state)),
+                                },
+                            };
+                            match command {
+                                DelayedCommand::Reschedule(new_deadline) => deadline = new_deadline,
+                                DelayedCommand::Cancel => return Err(PostmasterError::Cancelled),
+                            }
+                        }
+                    }
+                }
+
+                #[cfg(target_os = "none")]
+                fn acquire_delayed_slot() -> Result<usize, PostmasterError> {
+                    let slot = POSTMASTER
+                        .delayed_slots
+                        .iter()
+                        .position(|slot| {
+                            slot.holders
+                                .compare_exchange(
+                                    0,
+                                    HANDLE_HELD | TASK_RUNNING,
+                                    Ordering::Acquire,
+                                    Ordering::Relaxed,
+                                )
+                                .is_ok()
+                        })
+                        .ok_or(PostmasterError::DelayedMessagePoolFull)?;
+                    let delayed_slot = &POSTMASTER.delayed_slots[slot];
+                    delayed_slot.state.store(PENDING, Ordering::Release);
+                    delayed_slot.command.reset();
+                    delayed_slot.outcome.reset();
+                    Ok(slot)
+                }
+
+                #[cfg(target_os = "none")]
+                pub(super) fn release_delayed_slot(slot: usize, holder: u8) {
+                    POSTMASTER.delayed_slots[slot]
+                        .holders
+                        .fetch_and(!holder, Ordering::Release);
+                }
+
+                pub(super) fn get_diagnostics() -> super::Diagnostics{
+                    super::Diagnostics {
+                        messages_sent: POSTMASTER.messages_sent.load(Ordering::Relaxed),
+                        send_failures: POSTMASTER.send_failures.load(Ordering::Relaxed),
+                        delayed_send_failures: POSTMASTER
+                            .delayed_send_failures
+                            .load(Ordering::Relaxed),
+                    }
+                }
+
+                pub(super) fn set_timeout(timeout_us: u32) {
+                    POSTMASTER.timeout_us.store(timeout_us, Ordering::Relaxed)
+                }
 
-                #[cfg(target_os = "none")]
-                type Mailbox = DynamicSender<'static, Message>;
                 #[cfg(not(target_os = "none"))]
-                type Mailbox = Sender<Message>;
+                pub(super) fn set_supervision(strategy: RestartStrategy, limit: RestartLimit) {
+                    let mut policy = POSTMASTER.supervision.lock().unwrap();
+                    policy.strategy = strategy;
+                    policy.limit = limit;
+                }
 
-                pub(super) async fn register(
+                #[cfg(not(target_os = "none"))]
+                pub(super) fn report_supervision_events(
                     address: $address_enum,
-                    mailbox: Mailbox,
-                ) -> Result<(), PostmasterError> {
-                    let mut senders = POSTMASTER.senders.lock().await;
-                    if senders[address as usize].is_none() {
-                        senders[address as usize].replace(mailbox);
-                        Ok(())
-                    } else {
-                        return Err(PostmasterError::AddressAlreadyTaken);
-                    }
+                    into_payload: fn(SupervisionEvent<$address_enum>) -> $payload_enum,
+                ) {
+                    POSTMASTER
+                        .supervision
+                        .lock()
+                        .unwrap()
+                        .events
+                        .replace((address, into_payload));
                 }
 
-                pub(super) async fn send_internal(
-                    destination: $address_enum,
-                    message: Message,
-                    timeout: Option<Duration>,
-                ) -> Result<(), PostmasterError> {
-                    let timeout = match timeout {
-                        Some(duration) => duration,
-                        None => Duration::from_micros(
-                            POSTMASTER.timeout_us.load(Ordering::Relaxed).into(),
-                        ),
-                    };
-                    #[cfg(not(target_os = "none"))]
-                    evaluate_diagnostics(tokio::time::timeout(timeout, async {
-                        match &POSTMASTER.senders.lock().await[destination as usize] {
-                            None => Err(PostmasterError::NoRecipient),
-                            Some(sender) => {
-                                sender.send(message).await?;
-                                Ok(())
-                            }
-                        }
-                    })
-                    .await
-                    .map_err(|_| PostmasterError::Timeout)?)?;
-
-                    #[cfg(target_os = "none")]
-                    evaluate_diagnostics(
-                        async {
-                            match &POSTMASTER.senders.lock().await[destination as usize] {
-                                None => Err(PostmasterError::NoRecipient),
-                                Some(sender) => {
-                                    sender.send(message).await;
-                                    Ok(())
+                /// Runs a supervised Agent, restarting it according to the supervision policy whenever its main loop fails.
+                /// The Agent's registered mailbox stays with the supervisor for its whole lifetime, with messages being forwarded one at a time into an inbox which each incarnation of the Agent takes over from the last.
+                /// Supervision ends when the mailbox closes (i.e. the address has been deregistered) or the Agent exceeds its restart limit.
+                #[cfg(not(target_os = "none"))]
+                pub(super) async fn supervise<A, M, C, CF, R, RF>(
+                    source: ContextSource,
+                    mut mailbox: Receiver<Message>,
+                    idle: Arc<Notify>,
+                    mut agent: A,
+                    narrow: fn(Message) -> Option<M>,
+                    create: C,
+                    run: R,
+                ) where
+                    A: Send + 'static,
+                    M: Send + 'static,
+                    C: Fn() -> CF,
+                    CF: core::future::Future<Output = A>,
+                    R: Fn(A, post_haste::agent::Inbox<M>) -> RF,
+                    RF: core::future::Future<Output = ()> + Send + 'static,
+                {
+                    let address = source_address(&source);
+                    let generation = source_generation(&source);
+                    let mut restart_all = POSTMASTER.restart_all.subscribe();
+                    let mut restarts = std::collections::VecDeque::new();
+                    let mut pending = None;
+                    // The inbox outlives each incarnation of the Agent, so that a message forwarded into it but not yet received survives a restart
+                    let (inbox_sender, inbox) = channel::<M>(1);
+                    let inbox = Arc::new(std::sync::Mutex::new(inbox));
+                    loop {
+                        let mut task = tokio::task::spawn(run(agent, post_haste::agent::Inbox::shared(inbox.clone(), idle.clone()).with_latency(latency_histograms(address)).with_source::<$address_enum>(authorise(address, generation)).with_shutdown_flag(shutdown_flag())));
+                        // Stopping the supervisor (e.g. during shutdown) also stops the Agent
+                        let _abort_guard = AbortOnDrop(task.abort_handle());
+                        let failed = loop {
+                            tokio::select! {
+                                biased;
+                                _ = &mut task => break Some(true),
+                                _ = restart_all.changed() => {
+                                    task.abort();
+                                    let _ = (&mut task).await;
+                                    break Some(false);
+                                }
+                                permit = inbox_sender.reserve(), if pending.is_some() => {
+                                    if let Ok(permit) = permit {
+                                        permit.send(pending.take().unwrap());
+                                    }
+                                }
+                                message = mailbox.recv(), if pending.is_none() => match message {
+                                    Some(message) => pending = narrow(message),
+                                    None => break None,
                                 }
                             }
+                        };
+                        let Some(failed) = failed else {
+                            // The mailbox has closed, so pass the closure on to the Agent and stop supervising
+                            drop(inbox_sender);
+                            let _ = task.await;
+                            return;
+                        };
+
+                        let policy = *POSTMASTER.supervision.lock().unwrap();
+                        let now = Instant::now();
+                        restarts.retain(|restart| now.duration_since(*restart) < policy.limit.window);
+                        if restarts.len() >= policy.limit.max_restarts as usize {
+                            let _ = deregister(address, None);
+                            report_supervision_event(policy, SupervisionEvent::GaveUp { address }).await;
+                            return;
                         }
-                        .with_timeout(timeout)
-                        .await?,
-                    )?;
-                    Ok(())
+                        restarts.push_back(now);
+                        if failed && policy.strategy == RestartStrategy::OneForAll {
+                            POSTMASTER.restart_all.send_modify(|generation| *generation += 1);
+                            restart_all.borrow_and_update();
+                        }
+
+                        agent = create().await;
+                        report_supervision_event(
+                            policy,
+                            SupervisionEvent::Restarted {
+                                address,
+                                restarts: restarts.len() as u32,
+                            },
+                        )
+                        .await;
+                    }
                 }
 
-                pub(super) fn try_send_internal(
-                    destination: $address_enum,
-                    message: Message,
-                ) -> Result<(), PostmasterError> {
-                    evaluate_diagnostics(
-                        match &POSTMASTER.senders.try_lock()?[destination as usize] {
-                            None => Err(PostmasterError::NoRecipient),
-                            Some(sender) => {
-                                sender.try_send(message)?;
-                                Ok(())
-                            }
-                        },
-                    )
+                #[cfg(not(target_os = "none"))]
+                struct AbortOnDrop(task::AbortHandle);
+
+                #[cfg(not(target_os = "none"))]
+                impl Drop for AbortOnDrop {
+                    fn drop(&mut self) {
+                        self.0.abort();
+                    }
                 }
 
-                pub(super) async fn spawn_delayed_send(
-                    destination: $address_enum,
-                    message: Message,
-                    delay: Duration,
-                    timeout: Option<Duration>,
-                ) -> Result<(), PostmasterError> {
+                #[cfg(not(target_os = "none"))]
+                pub(super) fn track_agent(address: $address_enum, task: task::JoinHandle<()>, idle: Arc<Notify>) {
+                    POSTMASTER.agents.lock().unwrap()[address.index()].replace(AgentTask { task, idle });
+                }
 
-                    #[cfg(not(target_os = "none"))]
-                    {
-                        let _ = tokio::spawn(delayed_send(destination, message, delay, timeout));
-                        Ok(())
+                #[cfg(not(target_os = "none"))]
+                pub(super) fn shutdown_flag() -> &'static AtomicBool {
+                    &POSTMASTER.shutting_down
+                }
+
+                #[cfg(not(target_os = "none"))]
+                pub(super) async fn shutdown(timeout: Duration) -> Result<(), PostmasterError> {
+                    let deadline = Instant::now() + timeout;
+                    POSTMASTER.shutting_down.store(true, Ordering::Relaxed);
+                    // Dropping the senders closes every mailbox, so each Agent receives what is left in its inbox followed by `Delivery::Shutdown`
+                    for index in 0..ADDRESS_COUNT {
+                        close_route(index);
                     }
-                    #[cfg(target_os = "none")]
-                    if let Some(spawner) = *POSTMASTER.spawner.borrow(){
-                            Ok(spawner.spawn(
-                                delayed_send(destination, message, delay, timeout)
-                                .map_err(|e| PostmasterError::from(e))?)
-                            )
-                        } else {
-                            Err(PostmasterError::SpawnerNotSet)
-                        }
+                    let agents = core::mem::replace(
+                        &mut *POSTMASTER.agents.lock().unwrap(),
+                        [const { None }; ADDRESS_COUNT],
+                    );
 
+                    let mut result = Ok(());
+                    for AgentTask { mut task, idle } in agents.into_iter().flatten() {
+                        // An Agent whose task has already ended (e.g. by panicking) has nothing left to finish
+                        let ended = time::timeout_at(deadline, async {
+                            tokio::select! {
+                                _ = idle.notified() => false,
+                                _ = &mut task => true,
+                            }
+                        })
+                        .await
+                        .unwrap_or_else(|_| {
+                            result = Err(PostmasterError::Timeout);
+                            false
+                        });
+                        if !ended {
+                            task.abort();
+                            let _ = task.await;
+                        }
+                    }
+                    result
                 }
 
-                #[cfg_attr(target_os = "none", task(pool_size=DELAYED_MESSAGE_POOL_SIZE))]
-                pub(super) async fn delayed_send(
-                    destination: $address_enum,
-                    message: Message,
-                    delay: Duration,
-                    timeout: Option<Duration>,
-                ) {
-                    sleep(delay).await;
-                    let source = message.source;
-                    match send_internal(destination, message, timeout).await {
-                        Ok(_) => (),
-                        Err(error) => (), // TODO: Can we find a way to convey back to the source that the sending failed?
+                #[cfg(not(target_os = "none"))]
+                pub(super) fn reset() {
+                    for index in 0..ADDRESS_COUNT {
+                        close_route(index);
+                    }
+                    let agents = core::mem::replace(
+                        &mut *POSTMASTER.agents.lock().unwrap(),
+                        [const { None }; ADDRESS_COUNT],
+                    );
+                    for agent in agents.into_iter().flatten() {
+                        agent.task.abort();
+                    }
+                    POSTMASTER.timeout_us.store($timeout_us, Ordering::Relaxed);
+                    POSTMASTER.messages_sent.store(0, Ordering::Relaxed);
+                    POSTMASTER.send_failures.store(0, Ordering::Relaxed);
+                    POSTMASTER.delayed_send_failures.store(0, Ordering::Relaxed);
+                    for stats in &POSTMASTER.address_stats {
+                        stats.clear();
                     }
+                    let _ = POSTMASTER.tap.take();
+                    let _ = POSTMASTER.recorder.take();
+                    *POSTMASTER.supervision.lock().unwrap() = SupervisionPolicy {
+                        strategy: RestartStrategy::default(),
+                        limit: RestartLimit::default(),
+                        events: None,
+                    };
+                    POSTMASTER.shutting_down.store(false, Ordering::Relaxed);
                 }
 
-                #[cfg_attr(target_os = "none", task(pool_size=DELAYED_MESSAGE_POOL_SIZE))]
-                pub(super) async fn delayed_try_send(
-                    destination: $address_enum,
-                    message: Message,
-                    delay: Duration,
-                ) {
-                    sleep(delay).await;
-                    match try_send_internal(destination, message) {
-                        Ok(_) => (),
-                        Err(error) => (), // TODO: Can we find a way to convey back to the source that the sending failed?
+                #[cfg(not(target_os = "none"))]
+                fn accepting_messages() -> Result<(), PostmasterError> {
+                    if POSTMASTER.shutting_down.load(Ordering::Relaxed) {
+                        Err(PostmasterError::ShuttingDown)
+                    } else {
+                        Ok(())
                     }
                 }
 
-                pub(super) fn get_diagnostics() -> super::Diagnostics{
-                    super::Diagnostics {
-                        messages_sent: POSTMASTER.messages_sent.load(Ordering::Relaxed),
-                        send_failures: POSTMASTER.send_failures.load(Ordering::Relaxed),
+                #[cfg(not(target_os = "none"))]
+                struct AgentTask {
+                    task: task::JoinHandle<()>,
+                    idle: Arc<Notify>,
+                }
+
+                #[cfg(not(target_os = "none"))]
+                async fn report_supervision_event(
+                    policy: SupervisionPolicy,
+                    event: SupervisionEvent<$address_enum>,
+                ) {
+                    if let Some((destination, into_payload)) = policy.events {
+                        let source = match event {
+                            SupervisionEvent::Restarted { address, .. } => address,
+                            SupervisionEvent::GaveUp { address } => address,
+                        };
+                        let _ = send_internal(destination, Message::new(source, into_payload(event)), None).await;
                     }
                 }
 
-                pub(super) fn set_timeout(timeout_us: u32) {
-                    POSTMASTER.timeout_us.store(timeout_us, Ordering::Relaxed)
+                #[cfg(not(target_os = "none"))]
+                #[derive(Clone, Copy)]
+                struct SupervisionPolicy {
+                    strategy: RestartStrategy,
+                    limit: RestartLimit,
+                    events: Option<($address_enum, fn(SupervisionEvent<$address_enum>) -> $payload_enum)>,
                 }
 
                 #[cfg(target_os = "none")]
@@ -510,64 +2592,546 @@ macro_rules! init_postmaster {
 
                 #[cfg(not(target_os = "none"))]
                 struct Postmaster {
-                    senders: Mutex<[Option<Mailbox>; ADDRESS_COUNT]>,
+                    routes: [RoutingSlot<($address_enum, Mailbox, Accepts)>; ADDRESS_COUNT],
+                    /// How many times each address has been registered or deregistered, which tells whether a `SourceToken` is current
+                    generations: [AtomicU32; ADDRESS_COUNT],
                     timeout_us: AtomicU32,
                     messages_sent: AtomicUsize,
                     send_failures: AtomicUsize,
+                    delayed_send_failures: AtomicUsize,
+                    address_stats: [AddressStats; ADDRESS_COUNT],
+                    tap: RoutingSlot<Tap>,
+                    recorder: RoutingSlot<post_haste::record::Recorder<$address_enum, $payload_enum>>,
+                    supervision: std::sync::Mutex<SupervisionPolicy>,
+                    restart_all: watch::Sender<u64>,
+                    agents: std::sync::Mutex<[Option<AgentTask>; ADDRESS_COUNT]>,
+                    shutting_down: AtomicBool,
                 }
                 #[cfg(not(target_os = "none"))]
                 static POSTMASTER: Lazy<Postmaster> = Lazy::new(|| Postmaster {
-                    senders: Mutex::new([const { None }; ADDRESS_COUNT]),
+                    routes: [const { RoutingSlot::new() }; ADDRESS_COUNT],
+                    generations: [const { AtomicU32::new(0) }; ADDRESS_COUNT],
                     timeout_us: AtomicU32::new($timeout_us),
                     messages_sent: AtomicUsize::new(0),
                     send_failures: AtomicUsize::new(0),
+                    delayed_send_failures: AtomicUsize::new(0),
+                    address_stats: [const { AddressStats::new() }; ADDRESS_COUNT],
+                    tap: RoutingSlot::new(),
+                    recorder: RoutingSlot::new(),
+                    supervision: std::sync::Mutex::new(SupervisionPolicy {
+                        strategy: RestartStrategy::default(),
+                        limit: RestartLimit::default(),
+                        events: None,
+                    }),
+                    restart_all: watch::Sender::new(0),
+                    agents: std::sync::Mutex::new([const { None }; ADDRESS_COUNT]),
+                    shutting_down: AtomicBool::new(false),
                 });
 
                 #[cfg(target_os = "none")]
-                unsafe impl Sync for Postmaster<'_> {}
+                struct ReplySlot {
+                    in_use: AtomicBool,
+                    generation: AtomicU32,
+                    reply: Signal<NoopRawMutex, Option<$payload_enum>>,
+                }
+
+                #[cfg(target_os = "none")]
+                impl ReplySlot {
+                    const fn new() -> Self {
+                        Self {
+                            in_use: AtomicBool::new(false),
+                            generation: AtomicU32::new(0),
+                            reply: Signal::new(),
+                        }
+                    }
+                }
+
+                /// Claims one of the reply slots for the duration of a request, releasing it again when dropped.
+                /// Releasing a slot bumps its generation, so that a late reply to an abandoned request can never be mistaken for a reply to the next one.
+                #[cfg(target_os = "none")]
+                struct ReplySlotGuard {
+                    index: usize,
+                }
+
+                #[cfg(target_os = "none")]
+                impl ReplySlotGuard {
+                    fn acquire() -> Result<Self, PostmasterError> {
+                        POSTMASTER
+                            .reply_slots
+                            .iter()
+                            .position(|slot| {
+                                slot.in_use
+                                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                                    .is_ok()
+                            })
+                            .map(|index| Self { index })
+                            .ok_or(PostmasterError::ReplyPoolFull)
+                    }
+                }
+
+                #[cfg(target_os = "none")]
+                impl Drop for ReplySlotGuard {
+                    fn drop(&mut self) {
+                        let reply_slot = &POSTMASTER.reply_slots[self.index];
+                        reply_slot.generation.fetch_add(1, Ordering::Relaxed);
+                        reply_slot.reply.reset();
+                        reply_slot.in_use.store(false, Ordering::Release);
+                    }
+                }
+
+                #[cfg(target_os = "none")]
+                pub(super) const HANDLE_HELD: u8 = 0b01;
+                #[cfg(target_os = "none")]
+                const TASK_RUNNING: u8 = 0b10;
+
+                /// Tracks a delayed message in the pool.
+                /// The slot is free again once neither the `DelayedMessageHandle` nor the delivery task hold it.
+                #[cfg(target_os = "none")]
+                struct DelayedSlot {
+                    holders: AtomicU8,
+                    state: AtomicU8,
+                    command: Signal<NoopRawMutex, DelayedCommand>,
+                    outcome: Signal<NoopRawMutex, Result<(), PostmasterError>>,
+                }
+
+                #[cfg(target_os = "none")]
+                impl DelayedSlot {
+                    const fn new() -> Self {
+                        Self {
+                            holders: AtomicU8::new(0),
+                            state: AtomicU8::new(PENDING),
+                            command: Signal::new(),
+                            outcome: Signal::new(),
+                        }
+                    }
+                }
+
+                /// Tracks a periodic message in the pool.
+                /// The generation is bumped whenever the slot is released, so that a stale `PeriodicMessageHandle` can't stop the slot's next occupant.
+                #[cfg(target_os = "none")]
+                struct PeriodicSlot {
+                    in_use: AtomicBool,
+                    generation: AtomicU32,
+                    stop: Signal<NoopRawMutex, ()>,
+                }
+
+                #[cfg(target_os = "none")]
+                impl PeriodicSlot {
+                    const fn new() -> Self {
+                        Self {
+                            in_use: AtomicBool::new(false),
+                            generation: AtomicU32::new(0),
+                            stop: Signal::new(),
+                        }
+                    }
+                }
+
+                #[cfg(target_os = "none")]
+                unsafe impl Sync for Postmaster {}
                 #[cfg(target_os = "none")]
-                struct Postmaster<'a> {
-                    senders:
-                        Mutex<NoopRawMutex, [Option<DynamicSender<'a, Message>>; ADDRESS_COUNT]>,
+                struct Postmaster {
+                    routes: [RoutingSlot<($address_enum, Mailbox, Accepts)>; ADDRESS_COUNT],
+                    /// How many times each address has been registered or deregistered, which tells whether a `SourceToken` is current
+                    generations: [AtomicU32; ADDRESS_COUNT],
                     timeout_us: AtomicU32,
                     spawner: RefCell<Option<Spawner>>,
+                    reply_slots: [ReplySlot; REPLY_POOL_SIZE],
+                    delayed_slots: [DelayedSlot; DELAYED_MESSAGE_POOL_SIZE],
+                    periodic_slots: [PeriodicSlot; PERIODIC_MESSAGE_POOL_SIZE],
                     messages_sent: AtomicUsize,
                     send_failures: AtomicUsize,
+                    delayed_send_failures: AtomicUsize,
+                    address_stats: [AddressStats; ADDRESS_COUNT],
+                    tap: RoutingSlot<Tap>,
+                    recorder: RoutingSlot<post_haste::record::Recorder<$address_enum, $payload_enum>>,
                 }
 
                 #[cfg(target_os = "none")]
                 static POSTMASTER: Postmaster = Postmaster {
-                    senders: Mutex::new([None; ADDRESS_COUNT]),
+                    routes: [const { RoutingSlot::new() }; ADDRESS_COUNT],
+                    generations: [const { AtomicU32::new(0) }; ADDRESS_COUNT],
                     timeout_us: AtomicU32::new(100),
                     spawner: RefCell::new(None),
+                    reply_slots: [const { ReplySlot::new() }; REPLY_POOL_SIZE],
+                    delayed_slots: [const { DelayedSlot::new() }; DELAYED_MESSAGE_POOL_SIZE],
+                    periodic_slots: [const { PeriodicSlot::new() }; PERIODIC_MESSAGE_POOL_SIZE],
                     messages_sent: AtomicUsize::new(0),
                     send_failures: AtomicUsize::new(0),
+                    delayed_send_failures: AtomicUsize::new(0),
+                    address_stats: [const { AddressStats::new() }; ADDRESS_COUNT],
+                    tap: RoutingSlot::new(),
+                    recorder: RoutingSlot::new(),
                 };
 
                 #[inline]
                 fn evaluate_diagnostics(
+                    destination: $address_enum,
                     result: Result<(), PostmasterError>,
                 ) -> Result<(), PostmasterError> {
+                    let stats = &POSTMASTER.address_stats[destination.index()];
                     result
                         .inspect(|_| {
                             POSTMASTER.messages_sent.fetch_add(1, Ordering::Relaxed);
+                            stats.delivered.fetch_add(1, Ordering::Relaxed);
                         })
-                        .inspect_err(|_| {
+                        .inspect_err(|error| {
                             POSTMASTER.send_failures.fetch_add(1, Ordering::Relaxed);
+                            stats.failures[*error as usize].fetch_add(1, Ordering::Relaxed);
                         })
                 }
 
-                async fn sleep(delay: Duration){
-                    #[cfg(target_os = "none")]
-                    Timer::after(delay).await;
+                fn note_queue_depth(destination: $address_enum, mailbox: &Mailbox) {
+                    if let Some(depth) = mailbox.queued() {
+                        POSTMASTER.address_stats[destination.index()]
+                            .high_water_mark
+                            .fetch_max(depth, Ordering::Relaxed);
+                    }
+                }
+
+                pub(super) fn get_address_diagnostics(address: $address_enum) -> super::AddressDiagnostics {
+                    let stats = &POSTMASTER.address_stats[address.index()];
+                    let mailbox = POSTMASTER.routes[address.index()].get();
+                    super::AddressDiagnostics {
+                        registered: mailbox.is_some(),
+                        delivered: stats.delivered.load(Ordering::Relaxed),
+                        failures: post_haste::FailureCounts::new(core::array::from_fn(|index| {
+                            stats.failures[index].load(Ordering::Relaxed)
+                        })),
+                        queue_depth: mailbox.and_then(|(_, mailbox, _)| mailbox.queued()),
+                        high_water_mark: stats.high_water_mark.load(Ordering::Relaxed),
+                        queueing_latency: stats.latency.queueing.snapshot(),
+                        handling_time: stats.latency.handling.snapshot(),
+                    }
+                }
+
+                pub(super) fn latency_histograms(address: $address_enum) -> &'static post_haste::latency::LatencyHistograms {
+                    &POSTMASTER.address_stats[address.index()].latency
+                }
+
+                struct AddressStats {
+                    delivered: AtomicUsize,
+                    failures: [AtomicUsize; PostmasterError::COUNT],
+                    high_water_mark: AtomicUsize,
+                    latency: post_haste::latency::LatencyHistograms,
+                }
+
+                impl AddressStats {
+                    const fn new() -> Self {
+                        Self {
+                            delivered: AtomicUsize::new(0),
+                            failures: [const { AtomicUsize::new(0) }; PostmasterError::COUNT],
+                            high_water_mark: AtomicUsize::new(0),
+                            latency: post_haste::latency::LatencyHistograms::new(),
+                        }
+                    }
+
                     #[cfg(not(target_os = "none"))]
-                    time::sleep(delay).await;
+                    fn clear(&self) {
+                        self.delivered.store(0, Ordering::Relaxed);
+                        for failures in &self.failures {
+                            failures.store(0, Ordering::Relaxed);
+                        }
+                        self.high_water_mark.store(0, Ordering::Relaxed);
+                        self.latency.clear();
+                    }
+                }
+
+                #[derive(Clone, Copy)]
+                struct Tap {
+                    sources: Option<[bool; ADDRESS_COUNT]>,
+                    destinations: Option<[bool; ADDRESS_COUNT]>,
+                    describe: Option<fn(&$payload_enum) -> &'static str>,
+                    sink: TapSink,
+                }
+
+                #[derive(Clone, Copy)]
+                pub(super) enum TapSink {
+                    Forward($address_enum, fn(super::TapRecord) -> $payload_enum),
+                    Call(fn(&super::TapRecord)),
+                }
+
+                pub(super) fn install_tap(builder: super::TapBuilder, sink: TapSink) {
+                    let _ = POSTMASTER.tap.take();
+                    let _ = POSTMASTER.tap.insert(Tap {
+                        sources: builder.sources,
+                        destinations: builder.destinations,
+                        describe: builder.describe,
+                        sink,
+                    });
+                }
+
+                pub(super) fn remove_tap() {
+                    let _ = POSTMASTER.tap.take();
+                }
+
+                /// A message which the tap is observing, to be reported once the outcome of sending it is known.
+                struct TapProbe {
+                    sink: TapSink,
+                    record: super::TapRecord,
+                }
+
+                impl TapProbe {
+                    fn deliver(mut self, outcome: Result<(), PostmasterError>) {
+                        self.record.outcome = outcome;
+                        match self.sink {
+                            TapSink::Call(callback) => callback(&self.record),
+                            TapSink::Forward(address, into_payload) => {
+                                // Forwarded straight into the mailbox rather than sent, so the tap never observes its own records
+                                if let Some((_, mailbox, accepts)) = POSTMASTER.routes[address.index()].get() {
+                                    let message = Message::new(self.record.source, into_payload(self.record));
+                                    if accepts(&message.payload) {
+                                        let _ = mailbox.try_send(message, Priority::NORMAL);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                #[inline]
+                fn tap_probe(
+                    source: $address_enum,
+                    destination: $address_enum,
+                    payload: &$payload_enum,
+                ) -> Option<TapProbe> {
+                    // Checked first so that sends don't touch the slot's reader count while no tap is installed
+                    if !POSTMASTER.tap.is_occupied() {
+                        return None;
+                    }
+                    let tap = POSTMASTER.tap.get()?;
+                    let observed = |filter: Option<[bool; ADDRESS_COUNT]>, address: $address_enum| {
+                        filter.is_none_or(|filter| filter[address.index()])
+                    };
+                    if !(observed(tap.sources, source) && observed(tap.destinations, destination)) {
+                        return None;
+                    }
+                    Some(TapProbe {
+                        sink: tap.sink,
+                        record: super::TapRecord {
+                            source,
+                            destination,
+                            payload: core::mem::discriminant(payload),
+                            description: tap.describe.map_or("", |describe| describe(payload)),
+                            timestamp: Instant::now(),
+                            outcome: Ok(()),
+                        },
+                    })
+                }
+
+                pub(super) fn start_recording(recorder: post_haste::record::Recorder<$address_enum, $payload_enum>) {
+                    let _ = POSTMASTER.recorder.take();
+                    let _ = POSTMASTER.recorder.insert(recorder.start());
+                }
+
+                pub(super) fn stop_recording() {
+                    let _ = POSTMASTER.recorder.take();
+                }
+
+                #[inline]
+                fn record_message(source: $address_enum, destination: $address_enum, payload: &$payload_enum) {
+                    // Checked first so that sends don't touch the slot's reader count while nothing is being recorded
+                    if !POSTMASTER.recorder.is_occupied() {
+                        return;
+                    }
+                    if let Some(recorder) = POSTMASTER.recorder.get() {
+                        recorder.record(&source, &destination, payload);
+                    }
+                }
+
+                #[cfg(not(target_os = "none"))]
+                pub(super) async fn replay(
+                    messages: impl IntoIterator<Item = super::RecordedMessage>,
+                    timing: post_haste::record::ReplayTiming,
+                ) -> Result<post_haste::record::ReplaySummary, PostmasterError> {
+                    timing.validate()?;
+                    let start = Instant::now();
+                    let mut summary = post_haste::record::ReplaySummary::default();
+                    for recorded in messages {
+                        if let Some(offset) = timing.offset(recorded.timestamp_us) {
+                            // Sleeping for the remainder rather than until `start + offset`, which can overflow
+                            time::sleep(offset.saturating_sub(start.elapsed())).await;
+                        }
+                        let message = Message::new(recorded.source, recorded.payload);
+                        match send_internal(recorded.destination, message, None).await {
+                            Ok(()) => summary.delivered += 1,
+                            Err(_) => summary.failed += 1,
+                        }
+                    }
+                    Ok(summary)
+                }
+
+                fn resolve_timeout(timeout: Option<Duration>) -> Duration {
+                    timeout.unwrap_or_else(|| {
+                        Duration::from_micros(POSTMASTER.timeout_us.load(Ordering::Relaxed).into())
+                    })
+                }
+            }
+        }
+    };
+    (@sources $address_enum:ident []) => {
+        impl Source for $address_enum {
+            fn address(&self) -> $address_enum {
+                *self
+            }
+        }
+
+        impl Source for &$address_enum {
+            fn address(&self) -> $address_enum {
+                **self
+            }
+        }
+
+        impl Owner for $address_enum {
+            fn address(&self) -> $address_enum {
+                *self
+            }
+        }
+
+        impl sealed::Sealed for $address_enum {}
+        impl sealed::Sealed for &$address_enum {}
+
+        #[doc(hidden)]
+        pub type ContextSource = $address_enum;
+
+        /// The source which an Agent registered with `register_agent!()` is given, from the token returned when its mailbox was registered.
+        #[doc(hidden)]
+        pub fn context_source(token: SourceToken) -> ContextSource {
+            token.address
+        }
+
+        fn authorise(address: $address_enum, _generation: u32) -> ContextSource {
+            address
+        }
+
+        fn source_address(source: &ContextSource) -> $address_enum {
+            *source
+        }
+
+        fn source_generation(_source: &ContextSource) -> u32 {
+            0
+        }
+    };
+    (@sources $address_enum:ident [authenticated]) => {
+        #[doc(hidden)]
+        pub type ContextSource = SourceToken;
+
+        /// The source which an Agent registered with `register_agent!()` is given, from the token returned when its mailbox was registered.
+        #[doc(hidden)]
+        pub fn context_source(token: SourceToken) -> ContextSource {
+            token
+        }
+
+        fn authorise(address: $address_enum, generation: u32) -> ContextSource {
+            SourceToken { address, generation }
+        }
+
+        fn source_address(source: &ContextSource) -> $address_enum {
+            source.address
+        }
+
+        fn source_generation(source: &ContextSource) -> u32 {
+            source.generation
+        }
+    };
+    (@topics $address_enum:ident, $payload_enum:ident, []) => {
+        /// Without topics there are no subscriptions to clear.
+        #[cfg(not(target_os = "none"))]
+        fn clear_subscriptions() {}
+    };
+    (@topics $address_enum:ident, $payload_enum:ident, [$topic_enum:ident]) => {
+        use super::$topic_enum;
+        use core::sync::atomic::Ordering;
+        use post_haste::PostHasteTopic as _;
+
+        const TOPIC_COUNT: usize = <$topic_enum as post_haste::PostHasteTopic>::COUNT;
+
+        /// Which addresses are subscribed to each topic, indexed by topic and then by address
+        static SUBSCRIPTIONS: [[AtomicBool; ADDRESS_COUNT]; TOPIC_COUNT] =
+            [const { [const { AtomicBool::new(false) }; ADDRESS_COUNT] }; TOPIC_COUNT];
+
+        /// Subscribe an address to a topic, so that messages published on the topic are delivered to it.
+        /// Subscribing does not require the address to have been registered yet, although messages will only be delivered once it has been.
+        /// Subscribing an address which is already subscribed has no effect.
+        pub fn subscribe(address: $address_enum, topic: $topic_enum) {
+            SUBSCRIPTIONS[topic.index()][address.index()].store(true, Ordering::Relaxed);
+        }
 
+        /// Unsubscribe an address from a topic, so that messages published on the topic are no longer delivered to it.
+        pub fn unsubscribe(address: $address_enum, topic: $topic_enum) {
+            SUBSCRIPTIONS[topic.index()][address.index()].store(false, Ordering::Relaxed);
+        }
+
+        /// Check whether an address is subscribed to a topic.
+        pub fn is_subscribed(address: $address_enum, topic: $topic_enum) -> bool {
+            SUBSCRIPTIONS[topic.index()][address.index()].load(Ordering::Relaxed)
+        }
+
+        /// Publish a message on a topic.
+        /// A copy of the message is sent to every registered address which is subscribed to the topic, apart from the source, so the publisher never needs to know who is listening.
+        /// Each copy is sent in turn using the Postmaster's default timeout, and the outcome for every subscriber is collected in the returned `DeliveryReport`.
+        /// Subscribers which have not registered a mailbox are skipped.
+        pub async fn publish<P: Clone + Into<$payload_enum>>(
+            topic: $topic_enum,
+            source: impl Source,
+            payload: P,
+        ) -> DeliveryReport {
+            let generation = source.generation();
+            let source = source.address();
+            let subscribers = &SUBSCRIPTIONS[topic.index()];
+            let mut report = DeliveryReport::new();
+            for destination in postmaster_internal::registered_addresses().into_iter().flatten() {
+                if destination.index() != source.index()
+                    && subscribers[destination.index()].load(Ordering::Relaxed)
+                {
+                    report.record(
+                        destination,
+                        postmaster_internal::send_internal(
+                            destination,
+                            Message { generation, ..Message::new(source, payload.clone().into()) },
+                            None,
+                        )
+                        .await,
+                    );
+                }
+            }
+            report
+        }
+
+        /// Unsubscribe every address from every topic.
+        #[cfg(not(target_os = "none"))]
+        fn clear_subscriptions() {
+            for subscribers in &SUBSCRIPTIONS {
+                for subscribed in subscribers {
+                    subscribed.store(false, Ordering::Relaxed);
                 }
             }
         }
     };
-    ($address_enum:ty, $payload_enum:ty) => {
-        init_postmaster!($address_enum, $payload_enum, 1000);
+    (@options ($name:ident, $address_enum:ident, $payload_enum:ident, $timeout_us: expr) {$($groups:tt)*} [$($topic_enum:ident)?] [$($sources:ident)?]) => {
+        $crate::init_postmaster!(@generate $name, $address_enum, $payload_enum, $timeout_us, { $($groups)* }, [$($topic_enum)?], [$($sources)?]);
+    };
+    (@options $base:tt {$($groups:tt)*} [$($topic_enum:ident)?] [$($sources:ident)?] groups: { $($new_groups:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::init_postmaster!(@options $base { $($new_groups)* } [$($topic_enum)?] [$($sources)?] $($($rest)*)?);
+    };
+    (@options $base:tt {$($groups:tt)*} [$($topic_enum:ident)?] [$($sources:ident)?] topics: $new_topic_enum:ident $(, $($rest:tt)*)?) => {
+        $crate::init_postmaster!(@options $base { $($groups)* } [$new_topic_enum] [$($sources)?] $($($rest)*)?);
+    };
+    (@options $base:tt {$($groups:tt)*} [$($topic_enum:ident)?] [$($sources:ident)?] sources: authenticated $(, $($rest:tt)*)?) => {
+        $crate::init_postmaster!(@options $base { $($groups)* } [$($topic_enum)?] [authenticated] $($($rest)*)?);
+    };
+    (@named $name:ident, $address_enum:ident, $payload_enum:ident) => {
+        $crate::init_postmaster!(@options ($name, $address_enum, $payload_enum, 1000) {} [] []);
+    };
+    (@named $name:ident, $address_enum:ident, $payload_enum:ident, $option:ident: $($options:tt)*) => {
+        $crate::init_postmaster!(@options ($name, $address_enum, $payload_enum, 1000) {} [] [] $option: $($options)*);
+    };
+    (@named $name:ident, $address_enum:ident, $payload_enum:ident, $timeout_us: expr $(, $($options:tt)*)?) => {
+        $crate::init_postmaster!(@options ($name, $address_enum, $payload_enum, $timeout_us) {} [] [] $($($options)*)?);
+    };
+    (name = $name:ident, $($arguments:tt)*) => {
+        $crate::init_postmaster!(@named $name, $($arguments)*);
+    };
+    ($($arguments:tt)*) => {
+        $crate::init_postmaster!(@named postmaster, $($arguments)*);
     };
 }
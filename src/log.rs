@@ -0,0 +1,42 @@
+//! Log lines for routing problems, emitted over `defmt` on Embassy targets when the `defmt` feature is enabled.
+//! Otherwise every function here does nothing.
+//!
+//! The feature requires the address enum to implement `defmt::Format`.
+
+use crate::PostmasterError;
+
+#[cfg(all(feature = "defmt", target_os = "none"))]
+pub trait Loggable: defmt::Format {}
+#[cfg(all(feature = "defmt", target_os = "none"))]
+impl<T: defmt::Format + ?Sized> Loggable for T {}
+
+#[cfg(not(all(feature = "defmt", target_os = "none")))]
+pub trait Loggable {}
+#[cfg(not(all(feature = "defmt", target_os = "none")))]
+impl<T: ?Sized> Loggable for T {}
+
+pub fn registration<A: Loggable>(address: &A, outcome: &Result<(), PostmasterError>) {
+    #[cfg(all(feature = "defmt", target_os = "none"))]
+    match outcome {
+        Ok(()) => defmt::info!("post-haste: registered {}", address),
+        Err(error) => defmt::warn!("post-haste: could not register {}: {}", address, error),
+    }
+    #[cfg(not(all(feature = "defmt", target_os = "none")))]
+    let _ = (address, outcome);
+}
+
+pub fn send_failure<A: Loggable>(source: &A, destination: &A, outcome: &Result<(), PostmasterError>) {
+    #[cfg(all(feature = "defmt", target_os = "none"))]
+    if let Err(error) = outcome {
+        defmt::warn!("post-haste: message from {} to {} failed: {}", source, destination, error);
+    }
+    #[cfg(not(all(feature = "defmt", target_os = "none")))]
+    let _ = (source, destination, outcome);
+}
+
+pub fn delayed_send_failure<A: Loggable>(source: &A, destination: &A, error: &PostmasterError) {
+    #[cfg(all(feature = "defmt", target_os = "none"))]
+    defmt::error!("post-haste: delayed message from {} to {} failed: {}", source, destination, error);
+    #[cfg(not(all(feature = "defmt", target_os = "none")))]
+    let _ = (source, destination, error);
+}
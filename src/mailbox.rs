@@ -0,0 +1,359 @@
+use crate::agent::Inbox;
+#[cfg(target_os = "none")]
+use core::task::{Context, Poll};
+#[cfg(target_os = "none")]
+use embassy_sync::{
+    blocking_mutex::raw::NoopRawMutex,
+    channel::{Channel, DynamicSender, TryReceiveError, TrySendError},
+};
+#[cfg(not(target_os = "none"))]
+use std::sync::Arc;
+#[cfg(not(target_os = "none"))]
+use tokio::sync::mpsc::{
+    Sender, channel,
+    error::{SendError, TrySendError},
+};
+
+/// The priority of a message.
+/// Messages with a higher priority are received before any pending messages with a lower priority, provided that the recipient has a mailbox with enough priority levels (see `register_agent!()`).
+/// Priorities beyond the number of levels in the recipient's mailbox are treated as its highest level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Priority(pub u8);
+
+impl Priority {
+    /// The priority given to messages which have not been given one explicitly.
+    pub const NORMAL: Self = Self(0);
+
+    fn level(self, levels: usize) -> usize {
+        (self.0 as usize).min(levels - 1)
+    }
+}
+
+/// The sending side of an Agent's mailbox, made up of one message queue per priority level.
+#[cfg(not(target_os = "none"))]
+pub struct Mailbox<T> {
+    levels: Arc<[Sender<T>]>,
+}
+
+#[cfg(not(target_os = "none"))]
+impl<T> Clone for Mailbox<T> {
+    fn clone(&self) -> Self {
+        Self {
+            levels: self.levels.clone(),
+        }
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+impl<T> Mailbox<T> {
+    /// Create a mailbox with the given number of priority levels, each of which can hold `capacity` messages.
+    /// Returns the mailbox along with the inbox which receives from it.
+    pub fn prioritised(levels: usize, capacity: usize) -> (Self, Inbox<T>)
+    where
+        T: Send + 'static,
+    {
+        assert!(levels > 0, "a mailbox needs at least one priority level");
+        let (senders, receivers) = (0..levels).map(|_| channel(capacity)).unzip::<_, _, Vec<_>, Vec<_>>();
+        (
+            Self {
+                levels: senders.into(),
+            },
+            Inbox::prioritised(receivers.into_boxed_slice()),
+        )
+    }
+
+    /// Push a message onto the queue for its priority, waiting for space if the queue is full.
+    pub async fn send(&self, message: T, priority: Priority) -> Result<(), SendError<T>> {
+        self.levels[priority.level(self.levels.len())]
+            .send(message)
+            .await
+    }
+
+    /// Push a message onto the queue for its priority, failing immediately if the queue is full.
+    pub fn try_send(&self, message: T, priority: Priority) -> Result<(), TrySendError<T>> {
+        self.levels[priority.level(self.levels.len())].try_send(message)
+    }
+
+    /// The number of messages waiting in the mailbox, across all priority levels.
+    pub fn queued(&self) -> Option<usize> {
+        Some(
+            self.levels
+                .iter()
+                .map(|level| level.max_capacity() - level.capacity())
+                .sum(),
+        )
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+impl<T> From<Sender<T>> for Mailbox<T> {
+    fn from(sender: Sender<T>) -> Self {
+        Self {
+            levels: Arc::new([sender]),
+        }
+    }
+}
+
+/// The receiving side of a set of message queues, one per priority level, which an `Inbox` can receive from without knowing how many levels there are or how large they are.
+#[cfg(target_os = "none")]
+pub trait ReceiveQueue<T> {
+    fn levels(&self) -> usize;
+    fn try_receive(&self, level: usize) -> Result<T, TryReceiveError>;
+    fn poll_receive(&self, level: usize, cx: &mut Context<'_>) -> Poll<T>;
+}
+
+/// A set of message queues, one per priority level, which can be shared between a `Mailbox` and an `Inbox` without knowing how many levels there are or how large they are.
+#[cfg(target_os = "none")]
+pub trait PriorityQueue<T>: ReceiveQueue<T> {
+    fn len(&self, level: usize) -> usize;
+    fn try_send(&self, level: usize, message: T) -> Result<(), TrySendError<T>>;
+    fn poll_ready_to_send(&self, level: usize, cx: &mut Context<'_>) -> Poll<()>;
+}
+
+/// Statically allocated storage for a mailbox with `LEVELS` priority levels, each of which can hold `CAPACITY` messages.
+#[cfg(target_os = "none")]
+pub struct PriorityChannel<T, const LEVELS: usize, const CAPACITY: usize> {
+    levels: [Channel<NoopRawMutex, T, CAPACITY>; LEVELS],
+}
+
+#[cfg(target_os = "none")]
+impl<T, const LEVELS: usize, const CAPACITY: usize> Default for PriorityChannel<T, LEVELS, CAPACITY> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_os = "none")]
+impl<T, const LEVELS: usize, const CAPACITY: usize> PriorityChannel<T, LEVELS, CAPACITY> {
+    pub const fn new() -> Self {
+        Self {
+            levels: [const { Channel::new() }; LEVELS],
+        }
+    }
+
+    /// The sending side of the channel, to be registered with the Postmaster.
+    pub fn mailbox(&'static self) -> Mailbox<T>
+    where
+        T: 'static,
+    {
+        Mailbox::Prioritised(self)
+    }
+
+    /// The receiving side of the channel, to be handed to the Agent.
+    pub fn inbox(&'static self) -> Inbox<T>
+    where
+        T: 'static,
+    {
+        Inbox::prioritised(self)
+    }
+}
+
+#[cfg(target_os = "none")]
+impl<T, const LEVELS: usize, const CAPACITY: usize> ReceiveQueue<T>
+    for PriorityChannel<T, LEVELS, CAPACITY>
+{
+    fn levels(&self) -> usize {
+        LEVELS
+    }
+
+    fn try_receive(&self, level: usize) -> Result<T, TryReceiveError> {
+        self.levels[level].try_receive()
+    }
+
+    fn poll_receive(&self, level: usize, cx: &mut Context<'_>) -> Poll<T> {
+        self.levels[level].poll_receive(cx)
+    }
+}
+
+#[cfg(target_os = "none")]
+impl<T, const LEVELS: usize, const CAPACITY: usize> PriorityQueue<T>
+    for PriorityChannel<T, LEVELS, CAPACITY>
+{
+    fn len(&self, level: usize) -> usize {
+        self.levels[level].len()
+    }
+
+    fn try_send(&self, level: usize, message: T) -> Result<(), TrySendError<T>> {
+        self.levels[level].try_send(message)
+    }
+
+    fn poll_ready_to_send(&self, level: usize, cx: &mut Context<'_>) -> Poll<()> {
+        self.levels[level].poll_ready_to_send(cx)
+    }
+}
+
+/// A view of a set of message queues which converts each message into another type as it is received, dropping any message which doesn't convert.
+/// Statically allocated alongside a `PriorityChannel` by `register_agent!()`, for Agents which only receive some of the payloads.
+#[cfg(target_os = "none")]
+pub struct Narrowed<R: 'static, T> {
+    queue: &'static dyn ReceiveQueue<R>,
+    narrow: fn(R) -> Option<T>,
+}
+
+#[cfg(target_os = "none")]
+impl<R, T> Narrowed<R, T> {
+    pub const fn new(queue: &'static dyn ReceiveQueue<R>, narrow: fn(R) -> Option<T>) -> Self {
+        Self { queue, narrow }
+    }
+
+    /// The receiving side of the queues, to be handed to the Agent.
+    pub fn inbox(&'static self) -> Inbox<T>
+    where
+        T: 'static,
+    {
+        Inbox::prioritised(self)
+    }
+}
+
+#[cfg(target_os = "none")]
+impl<R, T> ReceiveQueue<T> for Narrowed<R, T> {
+    fn levels(&self) -> usize {
+        self.queue.levels()
+    }
+
+    fn try_receive(&self, level: usize) -> Result<T, TryReceiveError> {
+        loop {
+            if let Some(message) = (self.narrow)(self.queue.try_receive(level)?) {
+                return Ok(message);
+            }
+        }
+    }
+
+    fn poll_receive(&self, level: usize, cx: &mut Context<'_>) -> Poll<T> {
+        loop {
+            match self.queue.poll_receive(level, cx) {
+                Poll::Ready(message) => {
+                    if let Some(message) = (self.narrow)(message) {
+                        return Poll::Ready(message);
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// The sending side of an Agent's mailbox: either a single message queue, or one message queue per priority level.
+#[cfg(target_os = "none")]
+pub enum Mailbox<T> {
+    Single(DynamicSender<'static, T>),
+    Prioritised(&'static dyn PriorityQueue<T>),
+}
+
+#[cfg(target_os = "none")]
+impl<T> Clone for Mailbox<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(target_os = "none")]
+impl<T> Copy for Mailbox<T> {}
+
+#[cfg(target_os = "none")]
+impl<T> Mailbox<T> {
+    /// Push a message onto the queue for its priority, waiting for space if the queue is full.
+    pub async fn send(&self, message: T, priority: Priority) {
+        match self {
+            Self::Single(sender) => sender.send(message).await,
+            Self::Prioritised(queue) => {
+                let level = priority.level(queue.levels());
+                let mut message = Some(message);
+                core::future::poll_fn(|cx| loop {
+                    match queue.try_send(level, message.take().unwrap()) {
+                        Ok(()) => return Poll::Ready(()),
+                        Err(TrySendError::Full(returned)) => {
+                            message.replace(returned);
+                            if queue.poll_ready_to_send(level, cx).is_pending() {
+                                return Poll::Pending;
+                            }
+                        }
+                    }
+                })
+                .await
+            }
+        }
+    }
+
+    /// Push a message onto the queue for its priority, failing immediately if the queue is full.
+    pub fn try_send(&self, message: T, priority: Priority) -> Result<(), TrySendError<T>> {
+        match self {
+            Self::Single(sender) => sender.try_send(message),
+            Self::Prioritised(queue) => queue.try_send(priority.level(queue.levels()), message),
+        }
+    }
+
+    /// The number of messages waiting in the mailbox, across all priority levels.
+    /// This is not known for mailboxes registered with a `DynamicSender`, which cannot see how full its channel is.
+    pub fn queued(&self) -> Option<usize> {
+        match self {
+            Self::Single(_) => None,
+            Self::Prioritised(queue) => Some((0..queue.levels()).map(|level| queue.len(level)).sum()),
+        }
+    }
+}
+
+#[cfg(target_os = "none")]
+impl<T> From<DynamicSender<'static, T>> for Mailbox<T> {
+    fn from(sender: DynamicSender<'static, T>) -> Self {
+        Self::Single(sender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mailbox, Priority};
+    use crate::latency::{Timestamp, Timestamped};
+
+    #[derive(Debug, PartialEq)]
+    struct Note(u8);
+
+    impl Timestamped for Note {
+        fn enqueued_at(&self) -> Timestamp {
+            Timestamp::default()
+        }
+    }
+
+    #[test]
+    fn priorities_beyond_the_highest_level_are_clamped() {
+        assert_eq!(Priority::NORMAL.level(3), 0);
+        assert_eq!(Priority(2).level(3), 2);
+        assert_eq!(Priority(3).level(3), 2);
+        assert_eq!(Priority(u8::MAX).level(3), 2);
+        assert_eq!(Priority(u8::MAX).level(1), 0);
+    }
+
+    #[test]
+    fn higher_priorities_are_received_first() {
+        let (mailbox, mut inbox) = Mailbox::prioritised(3, 4);
+        mailbox.try_send(Note(0), Priority::NORMAL).unwrap();
+        mailbox.try_send(Note(1), Priority(1)).unwrap();
+        mailbox.try_send(Note(2), Priority::NORMAL).unwrap();
+        mailbox.try_send(Note(3), Priority(9)).unwrap();
+        mailbox.try_send(Note(4), Priority(2)).unwrap();
+
+        let received: Vec<_> = core::iter::from_fn(|| inbox.try_recv().ok()).collect();
+        // The out of range priority shares the highest level, in the order sent
+        assert_eq!(received, [Note(3), Note(4), Note(1), Note(0), Note(2)]);
+    }
+
+    #[tokio::test]
+    async fn waiting_receivers_also_take_the_highest_priority_first() {
+        let (mailbox, mut inbox) = Mailbox::prioritised(2, 2);
+        mailbox.send(Note(0), Priority::NORMAL).await.unwrap();
+        mailbox.send(Note(1), Priority(1)).await.unwrap();
+        assert_eq!(inbox.recv().await, Some(Note(1)));
+        assert_eq!(inbox.recv().await, Some(Note(0)));
+    }
+
+    #[test]
+    fn a_single_level_mailbox_keeps_the_order_sent() {
+        let (mailbox, mut inbox) = Mailbox::prioritised(1, 3);
+        mailbox.try_send(Note(0), Priority::NORMAL).unwrap();
+        mailbox.try_send(Note(1), Priority(5)).unwrap();
+        mailbox.try_send(Note(2), Priority::NORMAL).unwrap();
+
+        let received: Vec<_> = core::iter::from_fn(|| inbox.try_recv().ok()).collect();
+        assert_eq!(received, [Note(0), Note(1), Note(2)]);
+    }
+}
@@ -0,0 +1,29 @@
+//! Building the payload enum passed to `init_postmaster!()` out of the message types of individual Agents.
+//! The conversions are normally derived, with `#[derive(PostHastePayload)]` on the payload enum.
+
+/// Implemented by a message type which is carried by one of the variants of the payload enum `P`.
+/// An Agent whose Message is `postmaster::Message<T>` only receives payloads carrying a `T`, and any attempt to send it another payload fails with `PostmasterError::UnsupportedPayload`.
+/// The payload enum is a variant of itself, carrying every payload.
+pub trait PayloadVariant<P>: Into<P> + Sized {
+    /// Whether the payload carries this message type.
+    fn matches(payload: &P) -> bool;
+
+    /// Take this message type out of the payload, handing the payload back if it carries something else.
+    fn narrow(payload: P) -> Result<Self, P>;
+}
+
+impl<P> PayloadVariant<P> for P {
+    fn matches(_payload: &P) -> bool {
+        true
+    }
+
+    fn narrow(payload: P) -> Result<Self, P> {
+        Ok(payload)
+    }
+}
+
+/// Implemented by the `postmaster::Message` generated by `init_postmaster!()`, for converting a message into one which carries a narrower payload (see `PayloadVariant`).
+pub trait Narrow<T>: Sized {
+    /// Convert the message, handing it back if its payload does not convert.
+    fn narrow(self) -> Result<T, Self>;
+}
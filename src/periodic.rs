@@ -0,0 +1,55 @@
+/// Describes what a periodic message should do when one or more ticks are missed.
+/// A tick is "missed" when the Postmaster was unable to deliver the message before the next tick was due, for example because the recipient's queue was full and the send had to wait for the timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedTickBehaviour {
+    /// Deliver the missed ticks back-to-back until the schedule has caught up.
+    /// The long-term rate of delivery is preserved, at the cost of a burst of messages.
+    Burst,
+    /// Restart the schedule from the moment the late tick was delivered.
+    /// Every tick after the late one is shifted back by the delay.
+    Delay,
+    /// Drop the missed ticks and carry on with the next tick which falls on the original schedule.
+    #[default]
+    Skip,
+}
+
+impl MissedTickBehaviour {
+    /// Calculate when the next tick is due.
+    /// All times are in microseconds, measured from the moment the periodic message was started.
+    /// `scheduled` is when the tick which has just been delivered was due, and `now` is when its delivery finished.
+    /// While the schedule is being kept, the next tick is always due exactly one period after the previous one, so no drift accumulates.
+    pub fn next_tick(&self, scheduled: u64, now: u64, period: u64) -> u64 {
+        let next = scheduled + period;
+        if now < next {
+            return next;
+        }
+        match self {
+            Self::Burst => next,
+            Self::Delay => now + period,
+            Self::Skip => scheduled + ((now - scheduled) / period + 1) * period,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MissedTickBehaviour;
+
+    #[test]
+    fn on_time_ticks_keep_the_schedule() {
+        for behaviour in [
+            MissedTickBehaviour::Burst,
+            MissedTickBehaviour::Delay,
+            MissedTickBehaviour::Skip,
+        ] {
+            assert_eq!(behaviour.next_tick(100, 150, 100), 200);
+        }
+    }
+
+    #[test]
+    fn late_ticks_follow_the_behaviour() {
+        assert_eq!(MissedTickBehaviour::Burst.next_tick(100, 350, 100), 200);
+        assert_eq!(MissedTickBehaviour::Delay.next_tick(100, 350, 100), 450);
+        assert_eq!(MissedTickBehaviour::Skip.next_tick(100, 350, 100), 400);
+    }
+}
@@ -0,0 +1,462 @@
+//! Recording the messages sent through the Postmaster, so that they can be replayed later (see `postmaster::start_recording()` and `postmaster::replay()`).
+//!
+//! A recording is a compact binary log.
+//! It begins with a header, followed by one entry per message: the time at which the message was sent (in microseconds since recording started), then its source, destination and payload.
+//! Times are encoded as LEB128 varints, and every other field is prefixed with its length in the same way.
+//! Addresses and payloads are encoded by their implementations of `Record`.
+
+use crate::dependencies::Instant;
+use core::marker::PhantomData;
+
+/// The bytes with which every recording begins.
+pub const HEADER: &[u8] = b"PHR\x01";
+
+/// The largest encoded entry which can be recorded, in bytes.
+/// Messages whose entries would be larger than this are left out of the recording.
+pub const MAX_ENTRY_SIZE: usize = 256;
+
+/// Implemented by address and payload types so that they can be recorded and replayed.
+pub trait Record: Sized {
+    /// Encode the value into the start of the buffer, returning the number of bytes used, or `None` if it does not fit.
+    fn encode(&self, buffer: &mut [u8]) -> Option<usize>;
+
+    /// Decode a value from the bytes produced by `encode()`, returning `None` if they are invalid.
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+/// Receives the bytes of a recording as they are produced.
+/// Each call to `write()` carries either the header or one complete entry.
+pub trait RecordSink: Sync {
+    fn write(&self, bytes: &[u8]);
+}
+
+/// Appends the recording to a writer (e.g. a `Vec<u8>` or a file).
+/// Errors from the writer are ignored, so that recording never interferes with sending.
+#[cfg(not(target_os = "none"))]
+impl<W: std::io::Write + Send> RecordSink for std::sync::Mutex<W> {
+    fn write(&self, bytes: &[u8]) {
+        if let Ok(mut writer) = self.lock() {
+            let _ = writer.write_all(bytes);
+        }
+    }
+}
+
+type EncodeEntry<A, P> = fn(u64, &A, &A, &P, &mut [u8]) -> Option<usize>;
+
+/// Records every message sent through the Postmaster into a sink.
+/// Pass one to `postmaster::start_recording()`.
+pub struct Recorder<A, P> {
+    sink: &'static dyn RecordSink,
+    encode: EncodeEntry<A, P>,
+    started: Instant,
+}
+
+impl<A, P> Clone for Recorder<A, P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A, P> Copy for Recorder<A, P> {}
+
+impl<A: Record, P: Record> Recorder<A, P> {
+    pub fn new(sink: &'static dyn RecordSink) -> Self {
+        Self {
+            sink,
+            encode: encode_entry::<A, P>,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl<A, P> Recorder<A, P> {
+    #[doc(hidden)]
+    pub fn start(mut self) -> Self {
+        self.started = Instant::now();
+        self.sink.write(HEADER);
+        self
+    }
+
+    #[doc(hidden)]
+    pub fn record(&self, source: &A, destination: &A, payload: &P) {
+        let timestamp_us = Instant::now().duration_since(self.started).as_micros() as u64;
+        let mut buffer = [0; MAX_ENTRY_SIZE];
+        if let Some(len) = (self.encode)(timestamp_us, source, destination, payload, &mut buffer) {
+            self.sink.write(&buffer[..len]);
+        }
+    }
+}
+
+fn encode_entry<A: Record, P: Record>(
+    timestamp_us: u64,
+    source: &A,
+    destination: &A,
+    payload: &P,
+    buffer: &mut [u8],
+) -> Option<usize> {
+    let mut len = write_varint(timestamp_us, buffer)?;
+    len += write_field(source, &mut buffer[len..])?;
+    len += write_field(destination, &mut buffer[len..])?;
+    len += write_field(payload, &mut buffer[len..])?;
+    Some(len)
+}
+
+/// Encodes the value with a varint length prefix.
+/// The value is encoded after room for the largest prefix it could need, and moved down afterwards if its prefix is shorter.
+fn write_field<T: Record>(value: &T, buffer: &mut [u8]) -> Option<usize> {
+    const PREFIX_ROOM: usize = varint_len(MAX_ENTRY_SIZE as u64);
+    let len = value.encode(buffer.get_mut(PREFIX_ROOM..)?)?;
+    let prefix_len = varint_len(len as u64);
+    buffer.copy_within(PREFIX_ROOM..PREFIX_ROOM + len, prefix_len);
+    write_varint(len as u64, buffer)?;
+    Some(prefix_len + len)
+}
+
+fn write_varint(mut value: u64, buffer: &mut [u8]) -> Option<usize> {
+    let mut len = 0;
+    loop {
+        let byte = buffer.get_mut(len)?;
+        *byte = (value & 0x7f) as u8;
+        value >>= 7;
+        len += 1;
+        if value == 0 {
+            return Some(len);
+        }
+        *byte |= 0x80;
+    }
+}
+
+const fn varint_len(mut value: u64) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
+/// A single message read back from a recording.
+pub struct RecordedMessage<A, P> {
+    /// When the message was sent, in microseconds since recording started
+    pub timestamp_us: u64,
+    pub source: A,
+    pub destination: A,
+    pub payload: P,
+}
+
+/// Enumeration of the ways in which reading a recording can fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The recording does not begin with the expected header
+    BadHeader,
+    /// The recording ends part of the way through an entry
+    Truncated,
+    /// An address or payload could not be decoded
+    InvalidValue,
+}
+
+/// Reads the messages back out of a recording, in the order in which they were sent.
+/// Iteration stops after the first error.
+pub struct LogReader<'a, A, P> {
+    bytes: &'a [u8],
+    failed: bool,
+    _types: PhantomData<fn() -> (A, P)>,
+}
+
+impl<'a, A: Record, P: Record> LogReader<'a, A, P> {
+    pub fn new(recording: &'a [u8]) -> Result<Self, DecodeError> {
+        let bytes = recording.strip_prefix(HEADER).ok_or(DecodeError::BadHeader)?;
+        Ok(Self {
+            bytes,
+            failed: false,
+            _types: PhantomData,
+        })
+    }
+
+    fn read_entry(&mut self) -> Result<RecordedMessage<A, P>, DecodeError> {
+        Ok(RecordedMessage {
+            timestamp_us: self.read_varint()?,
+            source: self.read_field()?,
+            destination: self.read_field()?,
+            payload: self.read_field()?,
+        })
+    }
+
+    fn read_field<T: Record>(&mut self) -> Result<T, DecodeError> {
+        let len = usize::try_from(self.read_varint()?).map_err(|_| DecodeError::Truncated)?;
+        if len > self.bytes.len() {
+            return Err(DecodeError::Truncated);
+        }
+        let (field, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        T::decode(field).ok_or(DecodeError::InvalidValue)
+    }
+
+    fn read_varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0;
+        for (index, byte) in self.bytes.iter().enumerate().take(10) {
+            value |= u64::from(byte & 0x7f) << (7 * index);
+            if byte & 0x80 == 0 {
+                self.bytes = &self.bytes[index + 1..];
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::Truncated)
+    }
+}
+
+impl<A: Record, P: Record> Iterator for LogReader<'_, A, P> {
+    type Item = Result<RecordedMessage<A, P>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.bytes.is_empty() {
+            return None;
+        }
+        let entry = self.read_entry();
+        self.failed = entry.is_err();
+        Some(entry)
+    }
+}
+
+/// How quickly `postmaster::replay()` re-sends the messages from a recording.
+#[cfg(not(target_os = "none"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayTiming {
+    /// With the same spacing as when they were recorded
+    Original,
+    /// With the spacing divided by the given factor, e.g. `Accelerated(10.0)` replays ten times faster than the original.
+    /// The factor must be a finite number greater than zero.
+    Accelerated(f64),
+    /// One after another, as quickly as they can be delivered
+    Immediate,
+}
+
+#[cfg(not(target_os = "none"))]
+impl ReplayTiming {
+    #[doc(hidden)]
+    pub fn validate(&self) -> Result<(), crate::PostmasterError> {
+        match self {
+            Self::Accelerated(factor) if !(factor.is_finite() && *factor > 0.0) => {
+                Err(crate::PostmasterError::InvalidReplayTiming)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Offsets too large to be represented are clamped to `Duration::MAX`.
+    #[doc(hidden)]
+    pub fn offset(&self, timestamp_us: u64) -> Option<core::time::Duration> {
+        match self {
+            Self::Original => Some(core::time::Duration::from_micros(timestamp_us)),
+            Self::Accelerated(factor) => Some(
+                core::time::Duration::try_from_secs_f64(timestamp_us as f64 / 1_000_000.0 / factor)
+                    .unwrap_or(core::time::Duration::MAX),
+            ),
+            Self::Immediate => None,
+        }
+    }
+}
+
+/// The outcome of replaying a recording with `postmaster::replay()`.
+#[cfg(not(target_os = "none"))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplaySummary {
+    /// The number of messages which were delivered
+    pub delivered: usize,
+    /// The number of messages which could not be delivered
+    pub failed: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Byte(u8);
+
+    impl Record for Byte {
+        fn encode(&self, buffer: &mut [u8]) -> Option<usize> {
+            *buffer.first_mut()? = self.0;
+            Some(1)
+        }
+
+        fn decode(bytes: &[u8]) -> Option<Self> {
+            match bytes {
+                [byte] => Some(Self(*byte)),
+                _ => None,
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Bytes(Vec<u8>);
+
+    impl Record for Bytes {
+        fn encode(&self, buffer: &mut [u8]) -> Option<usize> {
+            buffer.get_mut(..self.0.len())?.copy_from_slice(&self.0);
+            Some(self.0.len())
+        }
+
+        fn decode(bytes: &[u8]) -> Option<Self> {
+            Some(Self(bytes.to_vec()))
+        }
+    }
+
+    /// A timestamp, source, destination and payload.
+    type Entry = (u64, u8, u8, Vec<u8>);
+
+    fn recording(entries: &[Entry]) -> Vec<u8> {
+        let mut recording = HEADER.to_vec();
+        for (timestamp_us, source, destination, payload) in entries {
+            let mut buffer = [0; MAX_ENTRY_SIZE];
+            let len = encode_entry(
+                *timestamp_us,
+                &Byte(*source),
+                &Byte(*destination),
+                &Bytes(payload.clone()),
+                &mut buffer,
+            )
+            .unwrap();
+            recording.extend_from_slice(&buffer[..len]);
+        }
+        recording
+    }
+
+    fn read(recording: &[u8]) -> Vec<Result<Entry, DecodeError>> {
+        LogReader::<Byte, Bytes>::new(recording)
+            .unwrap()
+            .map(|entry| {
+                entry.map(|message| {
+                    (
+                        message.timestamp_us,
+                        message.source.0,
+                        message.destination.0,
+                        message.payload.0,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u64::MAX] {
+            let mut buffer = [0; 10];
+            let len = write_varint(value, &mut buffer).unwrap();
+            assert_eq!(len, varint_len(value));
+            let mut reader = LogReader::<Byte, Byte> {
+                bytes: &buffer[..len],
+                failed: false,
+                _types: PhantomData,
+            };
+            assert_eq!(reader.read_varint(), Ok(value));
+            assert!(reader.bytes.is_empty());
+        }
+    }
+
+    #[test]
+    fn fields_are_moved_down_to_their_prefix() {
+        // Short fields need a shorter prefix than the room left for it, so they are moved down after encoding
+        for len in [0, 1, 0x7f, 0x80, 200] {
+            let payload = Bytes((0..len).map(|byte| byte as u8).collect());
+            let mut buffer = [0xff; MAX_ENTRY_SIZE];
+            let written = write_field(&payload, &mut buffer).unwrap();
+            let prefix_len = varint_len(len as u64);
+            assert_eq!(written, prefix_len + len);
+            let mut reader = LogReader::<Byte, Bytes> {
+                bytes: &buffer[..written],
+                failed: false,
+                _types: PhantomData,
+            };
+            assert_eq!(reader.read_field::<Bytes>(), Ok(payload));
+        }
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let entries = [
+            (0, 1, 2, vec![]),
+            (0x80, 2, 1, vec![7; 5]),
+            (u64::MAX, 3, 4, vec![9; 200]),
+        ];
+        let read_back: Vec<_> = read(&recording(&entries))
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(read_back, entries);
+    }
+
+    #[test]
+    fn entries_which_do_not_fit_are_not_encoded() {
+        let mut buffer = [0; MAX_ENTRY_SIZE];
+        let payload = Bytes(vec![0; MAX_ENTRY_SIZE]);
+        assert_eq!(
+            encode_entry(0, &Byte(1), &Byte(2), &payload, &mut buffer),
+            None
+        );
+    }
+
+    #[test]
+    fn a_recording_without_the_header_is_rejected() {
+        assert!(matches!(
+            LogReader::<Byte, Bytes>::new(b"PHR\x02"),
+            Err(DecodeError::BadHeader)
+        ));
+        assert!(matches!(
+            LogReader::<Byte, Bytes>::new(&HEADER[..3]),
+            Err(DecodeError::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn reading_stops_at_a_truncated_entry() {
+        let complete = recording(&[(1, 1, 2, vec![1, 2, 3]), (2, 2, 1, vec![4, 5, 6])]);
+        let entry_len = (complete.len() - HEADER.len()) / 2;
+        for cut in 1..entry_len {
+            let read_back = read(&complete[..complete.len() - cut]);
+            assert_eq!(read_back.len(), 2);
+            assert!(read_back[0].is_ok());
+            assert_eq!(read_back[1], Err(DecodeError::Truncated));
+        }
+    }
+
+    #[test]
+    fn reading_stops_at_an_invalid_value() {
+        let mut recording = recording(&[(1, 1, 2, vec![]), (2, 2, 1, vec![])]);
+        // Give the first entry's source a length of 2, so that it swallows the destination's prefix
+        recording[HEADER.len() + 1] = 2;
+        assert_eq!(read(&recording), [Err(DecodeError::InvalidValue)]);
+    }
+
+    #[test]
+    fn accelerated_timing_must_have_a_positive_finite_factor() {
+        for factor in [0.0, -0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                ReplayTiming::Accelerated(factor).validate(),
+                Err(crate::PostmasterError::InvalidReplayTiming)
+            );
+        }
+        for timing in [
+            ReplayTiming::Original,
+            ReplayTiming::Accelerated(0.5),
+            ReplayTiming::Immediate,
+        ] {
+            assert_eq!(timing.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn accelerated_offsets_are_scaled_and_clamped() {
+        use core::time::Duration;
+
+        assert_eq!(
+            ReplayTiming::Accelerated(10.0).offset(1_000_000),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            ReplayTiming::Accelerated(f64::MIN_POSITIVE).offset(u64::MAX),
+            Some(Duration::MAX)
+        );
+        assert_eq!(ReplayTiming::Immediate.offset(1_000_000), None);
+    }
+}
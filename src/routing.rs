@@ -0,0 +1,204 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::Ordering;
+use portable_atomic::{AtomicU8, AtomicUsize};
+
+const EMPTY: u8 = 0;
+const WRITING: u8 = 1;
+const READY: u8 = 2;
+const CLOSING: u8 = 3;
+
+/// A single entry in the Postmaster's routing table.
+/// Lookups never block and never contend with lookups of other entries: they simply take a copy of the entry, while the slot guarantees that the entry cannot be removed from underneath them.
+/// Removing an entry waits only for lookups which are already copying it, which never await anything.
+#[doc(hidden)]
+pub struct RoutingSlot<T> {
+    state: AtomicU8,
+    readers: AtomicUsize,
+    entry: UnsafeCell<Option<T>>,
+}
+
+// Safety: the entry is only written while the slot is WRITING or CLOSING, and is only read while the slot is READY.
+// Removal waits until every reader which might have seen the slot READY has finished reading.
+unsafe impl<T: Send + Sync> Sync for RoutingSlot<T> {}
+
+impl<T> Default for RoutingSlot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> RoutingSlot<T> {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(EMPTY),
+            readers: AtomicUsize::new(0),
+            entry: UnsafeCell::new(None),
+        }
+    }
+
+    /// Fill the slot, handing the entry back if the slot is already occupied.
+    pub fn insert(&self, entry: T) -> Result<(), T> {
+        if self
+            .state
+            .compare_exchange(EMPTY, WRITING, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(entry);
+        }
+        unsafe { *self.entry.get() = Some(entry) };
+        self.state.store(READY, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Empty the slot, returning the entry which occupied it.
+    pub fn take(&self) -> Option<T> {
+        self.state
+            .compare_exchange(READY, CLOSING, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()?;
+        while self.readers.load(Ordering::SeqCst) != 0 {
+            core::hint::spin_loop();
+        }
+        let entry = unsafe { (*self.entry.get()).take() };
+        self.state.store(EMPTY, Ordering::SeqCst);
+        entry
+    }
+
+    pub fn is_occupied(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == READY
+    }
+}
+
+impl<T: Clone> RoutingSlot<T> {
+    /// Take a copy of the entry occupying the slot, if there is one.
+    pub fn get(&self) -> Option<T> {
+        self.readers.fetch_add(1, Ordering::SeqCst);
+        let entry = if self.state.load(Ordering::SeqCst) == READY {
+            unsafe { (*self.entry.get()).clone() }
+        } else {
+            None
+        };
+        self.readers.fetch_sub(1, Ordering::SeqCst);
+        entry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoutingSlot;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{Receiver, Sender, channel};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn an_occupied_slot_rejects_a_second_entry() {
+        let slot = RoutingSlot::new();
+        assert_eq!(slot.insert(1), Ok(()));
+        assert_eq!(slot.insert(2), Err(2));
+        assert_eq!(slot.get(), Some(1));
+    }
+
+    #[test]
+    fn a_slot_can_be_reused_after_take() {
+        let slot = RoutingSlot::new();
+        for entry in 0..3 {
+            assert!(!slot.is_occupied());
+            assert_eq!(slot.get(), None);
+            slot.insert(entry).unwrap();
+            assert!(slot.is_occupied());
+            assert_eq!(slot.get(), Some(entry));
+            assert_eq!(slot.take(), Some(entry));
+        }
+        assert_eq!(slot.take(), None);
+    }
+
+    #[test]
+    fn concurrent_gets_only_see_whole_entries() {
+        let slot = Arc::new(RoutingSlot::new());
+        let stop = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let slot = slot.clone();
+                let stop = stop.clone();
+                thread::spawn(move || {
+                    let mut seen = 0;
+                    while !stop.load(Ordering::Relaxed) {
+                        if let Some(entry) = slot.get() {
+                            let entry: Arc<Vec<usize>> = entry;
+                            assert!(entry.iter().all(|value| *value == entry[0]));
+                            seen += 1;
+                        }
+                    }
+                    seen
+                })
+            })
+            .collect();
+        for generation in 0..10_000 {
+            slot.insert(Arc::new(vec![generation; 16])).unwrap();
+            let entry = slot.take().unwrap();
+            assert_eq!(entry[0], generation);
+        }
+        stop.store(true, Ordering::Relaxed);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert!(!slot.is_occupied());
+    }
+
+    /// An entry whose clone blocks until it is released, so that a reader can be held part-way through `get()`.
+    struct Gate {
+        entered: Sender<()>,
+        release: Arc<Mutex<Receiver<()>>>,
+    }
+
+    impl Clone for Gate {
+        fn clone(&self) -> Self {
+            self.entered.send(()).unwrap();
+            self.release.lock().unwrap().recv().unwrap();
+            Self {
+                entered: self.entered.clone(),
+                release: self.release.clone(),
+            }
+        }
+    }
+
+    #[test]
+    fn take_waits_for_active_readers() {
+        let (entered, reader_entered) = channel();
+        let (release_reader, release) = channel();
+        let slot = Arc::new(RoutingSlot::new());
+        assert!(
+            slot.insert(Gate {
+                entered,
+                release: Arc::new(Mutex::new(release)),
+            })
+            .is_ok()
+        );
+
+        let reader = thread::spawn({
+            let slot = slot.clone();
+            move || slot.get().is_some()
+        });
+        reader_entered.recv().unwrap();
+
+        let taken = Arc::new(AtomicBool::new(false));
+        let taker = thread::spawn({
+            let slot = slot.clone();
+            let taken = taken.clone();
+            move || {
+                let entry = slot.take();
+                taken.store(true, Ordering::SeqCst);
+                entry.is_some()
+            }
+        });
+        thread::sleep(Duration::from_millis(50));
+        assert!(!taken.load(Ordering::SeqCst));
+        assert!(!slot.is_occupied());
+
+        release_reader.send(()).unwrap();
+        assert!(reader.join().unwrap());
+        assert!(taker.join().unwrap());
+        assert!(taken.load(Ordering::SeqCst));
+    }
+}
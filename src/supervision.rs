@@ -0,0 +1,40 @@
+use core::time::Duration;
+
+/// Determines which Agents are restarted when a supervised Agent fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartStrategy {
+    /// Only the Agent which failed is restarted.
+    #[default]
+    OneForOne,
+    /// Every supervised Agent is restarted when any one of them fails.
+    /// This is useful when Agents hold state which only makes sense alongside each other.
+    OneForAll,
+}
+
+/// The maximum rate at which a supervised Agent may be restarted.
+/// If an Agent needs restarting more than `max_restarts` times within `window`, the Postmaster gives up on it and deregisters its address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartLimit {
+    pub max_restarts: u32,
+    pub window: Duration,
+}
+
+impl Default for RestartLimit {
+    /// Three restarts within five seconds.
+    fn default() -> Self {
+        Self {
+            max_restarts: 3,
+            window: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Reports something the Postmaster has done to a supervised Agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupervisionEvent<A> {
+    /// The Agent at `address` was re-created and its main loop restarted.
+    /// `restarts` is the number of restarts within the current restart window, including this one.
+    Restarted { address: A, restarts: u32 },
+    /// The Agent at `address` exceeded its restart limit, so it has not been restarted and its address has been deregistered.
+    GaveUp { address: A },
+}
@@ -0,0 +1,102 @@
+//! Types for observing the traffic passing through the Postmaster (see `postmaster::tap()`).
+
+use crate::PostmasterError;
+use crate::dependencies::Instant;
+use core::fmt;
+use core::mem::Discriminant;
+
+/// A lightweight record of a single message sent through the Postmaster, as seen by a tap.
+pub struct TapRecord<A, P> {
+    /// The address from which the message was sent
+    pub source: A,
+    /// The address to which the message was sent
+    pub destination: A,
+    /// Which variant of the payload enum the message carried
+    pub payload: Discriminant<P>,
+    /// A description of the payload, as given by the tap's describe function (empty if none was given)
+    pub description: &'static str,
+    /// When the message was sent
+    pub timestamp: Instant,
+    /// Whether the message was delivered
+    pub outcome: Result<(), PostmasterError>,
+}
+
+impl<A: Clone, P> Clone for TapRecord<A, P> {
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source.clone(),
+            destination: self.destination.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<A: Copy, P> Copy for TapRecord<A, P> {}
+
+impl<A: fmt::Debug, P> fmt::Debug for TapRecord<A, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TapRecord")
+            .field("source", &self.source)
+            .field("destination", &self.destination)
+            .field("payload", &self.payload)
+            .field("description", &self.description)
+            .field("timestamp", &self.timestamp)
+            .field("outcome", &self.outcome)
+            .finish()
+    }
+}
+
+/// Formats the record as a single line, e.g. `Main -> DisplayAgent: Display (delivered)`.
+impl<A: fmt::Debug, P> fmt::Display for TapRecord<A, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} -> {:?}: ", self.source, self.destination)?;
+        if self.description.is_empty() {
+            write!(f, "{:?}", self.payload)?;
+        } else {
+            f.write_str(self.description)?;
+        }
+        match &self.outcome {
+            Ok(()) => f.write_str(" (delivered)"),
+            Err(error) => write!(f, " (failed: {error:?})"),
+        }
+    }
+}
+
+/// Extracts a tap record from a message, returning `None` for any other message.
+#[cfg(not(target_os = "none"))]
+pub type ExtractRecord<A, P, M> = fn(M) -> Option<TapRecord<A, P>>;
+
+/// A ready-made Agent which prints the traffic forwarded to it by a tap, one line per message, along with the time since it started.
+/// Its Config is a function which extracts the tap record from a message, returning `None` for any other message (which is ignored).
+/// Generated by `init_postmaster!()` as `postmaster::TapPrinter`; see `postmaster::tap()` for an example.
+#[cfg(not(target_os = "none"))]
+pub struct TapPrinter<A, P, M> {
+    extract: ExtractRecord<A, P, M>,
+    started: Instant,
+}
+
+#[cfg(not(target_os = "none"))]
+impl<A: fmt::Debug, P, M: crate::latency::Timestamped> crate::agent::Agent for TapPrinter<A, P, M> {
+    type Address = A;
+    type Message = M;
+    type Config = ExtractRecord<A, P, M>;
+
+    async fn create(_address: Self::Address, extract: Self::Config) -> Self {
+        Self {
+            extract,
+            started: Instant::now(),
+        }
+    }
+
+    async fn run(self, mut inbox: crate::agent::Inbox<Self::Message>) -> ! {
+        loop {
+            let Some(message) = inbox.recv().await else {
+                continue;
+            };
+            if let Some(record) = (self.extract)(message) {
+                let time = record.timestamp.saturating_duration_since(self.started);
+                println!("[{:>4}.{:06}] {record}", time.as_secs(), time.subsec_micros());
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Integration with the `tracing` crate.
+//! With the `tracing` feature enabled, the Postmaster emits spans and events for registration, for every send, and for delayed messages.
+//! Each message carries the span in which it was sent, so an Agent which handles the message within that span (see `TraceContext::instrument()`) places any messages it sends in turn in the same trace.
+//! Without the feature, the types in this module are zero-sized and every operation on them does nothing.
+//!
+//! The feature requires the address and payload enums to implement `Debug`, which is used to name them in spans.
+
+use crate::PostmasterError;
+use crate::dependencies::Duration;
+use core::future::Future;
+
+/// Types which the Postmaster can name in spans and events.
+/// With the `tracing` feature enabled this is any type which implements `Debug`, otherwise it is every type.
+#[cfg(feature = "tracing")]
+pub trait Traceable: core::fmt::Debug {}
+#[cfg(feature = "tracing")]
+impl<T: core::fmt::Debug + ?Sized> Traceable for T {}
+
+/// Types which the Postmaster can name in spans and events.
+/// With the `tracing` feature enabled this is any type which implements `Debug`, otherwise it is every type.
+#[cfg(not(feature = "tracing"))]
+pub trait Traceable {}
+#[cfg(not(feature = "tracing"))]
+impl<T: ?Sized> Traceable for T {}
+
+/// Implemented by message types which carry the span in which they were sent.
+pub trait Traced {
+    fn trace(&self) -> &TraceContext;
+}
+
+/// The span in which a message was sent.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "tracing"), derive(Default))]
+pub struct TraceContext {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+#[cfg(feature = "tracing")]
+impl Default for TraceContext {
+    fn default() -> Self {
+        Self {
+            span: tracing::Span::none(),
+        }
+    }
+}
+
+impl TraceContext {
+    #[cfg(feature = "tracing")]
+    pub fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    /// Run a closure within the span.
+    pub fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "tracing")]
+        return self.span.in_scope(f);
+        #[cfg(not(feature = "tracing"))]
+        f()
+    }
+
+    /// Run a future within the span.
+    /// Messages sent by the future are placed in the same trace as the message which carried this context.
+    pub fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        #[cfg(feature = "tracing")]
+        return tracing::Instrument::instrument(future, self.span.clone());
+        #[cfg(not(feature = "tracing"))]
+        future
+    }
+
+    #[doc(hidden)]
+    pub fn record_outcome(&self, outcome: &Result<(), PostmasterError>) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("outcome", tracing::field::debug(outcome));
+            match outcome {
+                Ok(()) => tracing::debug!(parent: &self.span, "message sent"),
+                Err(error) => tracing::warn!(parent: &self.span, ?error, "message could not be sent"),
+            }
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = outcome;
+    }
+}
+
+/// Opens the span for a single send, as a child of the current span.
+#[doc(hidden)]
+pub fn send_span<A: Traceable, P: Traceable>(
+    operation: &'static str,
+    source: &A,
+    destination: &A,
+    payload: &P,
+    timeout: Option<Duration>,
+) -> TraceContext {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::debug_span!(
+            "post_haste::send",
+            operation,
+            source = ?source,
+            destination = ?destination,
+            payload = %VariantName(payload),
+            timeout_us = tracing::field::Empty,
+            outcome = tracing::field::Empty,
+        );
+        if let Some(timeout) = timeout {
+            span.record("timeout_us", timeout.as_micros() as u64);
+        }
+        TraceContext { span }
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (operation, source, destination, payload, timeout);
+        TraceContext::default()
+    }
+}
+
+/// Opens the span covering a delayed message from the moment it is sent until it fires, as a child of the current span.
+#[doc(hidden)]
+pub fn delayed_span<A: Traceable, P: Traceable>(
+    source: &A,
+    destination: &A,
+    payload: &P,
+    delay: Duration,
+) -> TraceContext {
+    #[cfg(feature = "tracing")]
+    return TraceContext {
+        span: tracing::debug_span!(
+            "post_haste::delayed",
+            source = ?source,
+            destination = ?destination,
+            payload = %VariantName(payload),
+            delay_us = delay.as_micros() as u64,
+        ),
+    };
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (source, destination, payload, delay);
+        TraceContext::default()
+    }
+}
+
+#[doc(hidden)]
+pub fn delayed_fired() {
+    #[cfg(feature = "tracing")]
+    tracing::debug!("delayed message fired");
+}
+
+#[doc(hidden)]
+pub fn registration<A: Traceable>(address: &A, outcome: &Result<(), PostmasterError>) {
+    #[cfg(feature = "tracing")]
+    match outcome {
+        Ok(()) => tracing::info!(address = ?address, "address registered"),
+        Err(error) => tracing::warn!(address = ?address, ?error, "address could not be registered"),
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (address, outcome);
+}
+
+#[doc(hidden)]
+pub fn deregistration<A: Traceable>(address: &A, outcome: &Result<(), PostmasterError>) {
+    #[cfg(feature = "tracing")]
+    match outcome {
+        Ok(()) => tracing::info!(address = ?address, "address deregistered"),
+        Err(error) => tracing::warn!(address = ?address, ?error, "address could not be deregistered"),
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (address, outcome);
+}
+
+/// Displays only the variant name from a value's `Debug` output, leaving out any fields.
+#[cfg(feature = "tracing")]
+struct VariantName<'a, P: ?Sized>(&'a P);
+
+#[cfg(feature = "tracing")]
+impl<P: core::fmt::Debug + ?Sized> core::fmt::Display for VariantName<'_, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        struct UpToFields<'a, 'b> {
+            f: &'a mut core::fmt::Formatter<'b>,
+            done: bool,
+        }
+
+        impl core::fmt::Write for UpToFields<'_, '_> {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                if self.done {
+                    return Ok(());
+                }
+                match s.find(['(', ' ', '{']) {
+                    Some(end) => {
+                        self.done = true;
+                        self.f.write_str(&s[..end])
+                    }
+                    None => self.f.write_str(s),
+                }
+            }
+        }
+
+        core::fmt::write(&mut UpToFields { f, done: false }, format_args!("{:?}", self.0))
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+use tokio::time::Instant;
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Ping,
+    Undelivered,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PostHasteAddress)]
+enum Addresses {
+    Main,
+    Other,
+    Unregistered,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+const DELAY: Duration = Duration::from_secs(10);
+
+async fn send_delayed() -> postmaster::DelayedMessageHandle {
+    postmaster::message(Addresses::Main, Addresses::Other, Payloads::Ping)
+        .with_delay(DELAY)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_message_cancelled_before_its_delay_is_never_delivered() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    let handle = send_delayed().await;
+
+    harness.expect_no_message(Addresses::Main, DELAY / 2).await;
+    assert_eq!(handle.cancel(), Ok(()));
+    assert!(!handle.has_fired());
+    assert_eq!(handle.reschedule(DELAY), Err(PostmasterError::Cancelled));
+    assert_eq!(handle.cancel(), Err(PostmasterError::Cancelled));
+
+    harness.expect_no_message(Addresses::Main, DELAY * 2).await;
+    assert_eq!(handle.outcome().await, Err(PostmasterError::Cancelled));
+}
+
+#[tokio::test(start_paused = true)]
+async fn rescheduling_postpones_or_brings_forward_delivery() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    let start = Instant::now();
+
+    let postponed = send_delayed().await;
+    harness
+        .expect_no_message(Addresses::Main, Duration::from_secs(4))
+        .await;
+    assert_eq!(postponed.reschedule(DELAY), Ok(()));
+    harness
+        .expect_no_message(Addresses::Main, Duration::from_secs(9))
+        .await;
+    harness
+        .expect_message(Addresses::Main, Duration::from_secs(2), |_| true)
+        .await;
+    assert_eq!(start.elapsed(), Duration::from_secs(14));
+    assert!(postponed.has_fired());
+
+    let start = Instant::now();
+    let brought_forward = send_delayed().await;
+    assert_eq!(brought_forward.reschedule(Duration::from_secs(1)), Ok(()));
+    harness
+        .expect_message(Addresses::Main, DELAY, |_| true)
+        .await;
+    assert_eq!(start.elapsed(), Duration::from_secs(1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_fired_message_can_no_longer_be_cancelled_or_rescheduled() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    let handle = send_delayed().await;
+    assert!(!handle.has_fired());
+
+    harness
+        .expect_message(Addresses::Main, DELAY * 2, |_| true)
+        .await;
+    assert!(handle.has_fired());
+    assert_eq!(handle.cancel(), Err(PostmasterError::AlreadyFired));
+    assert_eq!(handle.reschedule(DELAY), Err(PostmasterError::AlreadyFired));
+    assert_eq!(handle.outcome().await, Ok(()));
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_reschedule_at_the_deadline_either_fails_or_is_honoured() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    let handle = send_delayed().await;
+
+    // The deadline has passed, but the task delivering the message may not have run yet
+    tokio::time::advance(DELAY).await;
+    match handle.reschedule(DELAY) {
+        Ok(()) => {
+            assert!(!handle.has_fired());
+            harness
+                .expect_no_message(Addresses::Main, DELAY - Duration::from_millis(1))
+                .await;
+            harness
+                .expect_message(Addresses::Main, Duration::from_millis(2), |_| true)
+                .await;
+        }
+        Err(error) => {
+            assert_eq!(error, PostmasterError::AlreadyFired);
+            harness
+                .expect_message(Addresses::Main, Duration::ZERO, |_| true)
+                .await;
+        }
+    }
+}
+
+/// Send a ping from `Main` to the destination after the delay, asking for a notice if it cannot be delivered.
+async fn send_with_failure_notice(destination: Addresses) -> postmaster::DelayedMessageHandle {
+    postmaster::message(destination, Addresses::Main, Payloads::Ping)
+        .with_delay(DELAY)
+        .with_failure_notice(Payloads::Undelivered)
+        .send()
+        .await
+        .unwrap()
+}
+
+fn is_failure_notice_from(destination: Addresses) -> impl Fn(&postmaster::Message) -> bool {
+    move |message| matches!(message.payload, Payloads::Undelivered) && message.source == destination
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_delayed_message_to_an_unregistered_address_sends_a_failure_notice() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    let handle = send_with_failure_notice(Addresses::Unregistered).await;
+
+    harness
+        .expect_no_message(Addresses::Main, DELAY - Duration::from_millis(1))
+        .await;
+    harness
+        .expect_message(
+            Addresses::Main,
+            Duration::from_millis(2),
+            is_failure_notice_from(Addresses::Unregistered),
+        )
+        .await;
+    assert_eq!(handle.outcome().await, Err(PostmasterError::NoRecipient));
+    let diagnostics = postmaster::get_diagnostics();
+    assert_eq!(diagnostics.delayed_send_failures, 1);
+    assert_eq!(diagnostics.send_failures, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_delayed_message_to_a_full_queue_sends_a_failure_notice() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness
+        .mock_all(&[Addresses::Main, Addresses::Other])
+        .await
+        .unwrap();
+    // Fill the mock's mailbox, which is never emptied as nothing is waiting on it
+    while postmaster::try_send(Addresses::Other, Addresses::Main, Payloads::Ping).is_ok() {}
+    let failures_while_filling = postmaster::get_diagnostics().send_failures;
+
+    let handle = send_with_failure_notice(Addresses::Other).await;
+    harness
+        .expect_message(
+            Addresses::Main,
+            DELAY * 2,
+            is_failure_notice_from(Addresses::Other),
+        )
+        .await;
+    assert_eq!(handle.outcome().await, Err(PostmasterError::Timeout));
+    let diagnostics = postmaster::get_diagnostics();
+    assert_eq!(diagnostics.delayed_send_failures, 1);
+    assert_eq!(diagnostics.send_failures, failures_while_filling + 1);
+}
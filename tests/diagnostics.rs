@@ -0,0 +1,86 @@
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Note,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PostHasteAddress)]
+enum Addresses {
+    Main,
+    Other,
+    Unregistered,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+async fn send_notes(count: usize) {
+    for _ in 0..count {
+        postmaster::send(Addresses::Main, Addresses::Other, Payloads::Note)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn an_address_counts_deliveries_and_tracks_its_queue_depth() {
+    let _isolation = postmaster::isolate();
+    let (sender, mut receiver) = mpsc::channel(4);
+    postmaster::register(Addresses::Main, sender).await.unwrap();
+
+    send_notes(3).await;
+    let diagnostics = postmaster::get_address_diagnostics(Addresses::Main);
+    assert!(diagnostics.registered);
+    assert_eq!(diagnostics.delivered, 3);
+    assert_eq!(diagnostics.queue_depth, Some(3));
+    assert_eq!(diagnostics.high_water_mark, 3);
+
+    receiver.recv().await.unwrap();
+    receiver.recv().await.unwrap();
+    send_notes(1).await;
+    let diagnostics = postmaster::get_address_diagnostics(Addresses::Main);
+    assert_eq!(diagnostics.delivered, 4);
+    assert_eq!(diagnostics.queue_depth, Some(2));
+    assert_eq!(diagnostics.high_water_mark, 3);
+    assert_eq!(diagnostics.failures.total(), 0);
+}
+
+#[tokio::test]
+async fn failures_are_counted_against_the_destination() {
+    let _isolation = postmaster::isolate();
+
+    for _ in 0..2 {
+        assert_eq!(
+            postmaster::try_send(Addresses::Unregistered, Addresses::Main, Payloads::Note),
+            Err(PostmasterError::NoRecipient)
+        );
+    }
+    let diagnostics = postmaster::get_address_diagnostics(Addresses::Unregistered);
+    assert!(!diagnostics.registered);
+    assert_eq!(diagnostics.delivered, 0);
+    assert_eq!(diagnostics.queue_depth, None);
+    assert_eq!(diagnostics.failures.get(PostmasterError::NoRecipient), 2);
+    assert_eq!(diagnostics.failures.total(), 2);
+}
+
+#[tokio::test]
+async fn every_address_is_listed_in_declaration_order() {
+    let _isolation = postmaster::isolate();
+    let (sender, _receiver) = mpsc::channel(4);
+    postmaster::register(Addresses::Other, sender)
+        .await
+        .unwrap();
+
+    let listed: Vec<_> = postmaster::address_diagnostics()
+        .map(|(address, diagnostics)| (address, diagnostics.registered))
+        .collect();
+    assert_eq!(
+        listed,
+        [
+            (Addresses::Main, false),
+            (Addresses::Other, true),
+            (Addresses::Unregistered, false),
+        ]
+    );
+}
@@ -0,0 +1,147 @@
+use post_haste::{PostHasteAddress, PostHasteTopic, PostmasterError, init_postmaster};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Note,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PostHasteAddress)]
+enum Addresses {
+    Main,
+    Alpha,
+    Beta,
+    Gamma,
+    Delta,
+}
+
+#[derive(Debug, Clone, Copy, PostHasteTopic)]
+enum Topics {
+    Weather,
+    News,
+}
+
+init_postmaster!(
+    Addresses,
+    Payloads,
+    groups: { ODD: [Alpha, Gamma] },
+    topics: Topics
+);
+
+/// Register plain inboxes at `Main`, `Alpha`, `Beta` and `Gamma`, leaving `Delta` unregistered.
+async fn register_all() -> [mpsc::Receiver<postmaster::Message>; 4] {
+    let mut receivers = Vec::new();
+    for address in [
+        Addresses::Main,
+        Addresses::Alpha,
+        Addresses::Beta,
+        Addresses::Gamma,
+    ] {
+        let (sender, receiver) = mpsc::channel(4);
+        postmaster::register(address, sender).await.unwrap();
+        receivers.push(receiver);
+    }
+    receivers.try_into().unwrap()
+}
+
+fn recipients(report: &postmaster::DeliveryReport) -> Vec<Addresses> {
+    report.iter().map(|(address, _)| address).collect()
+}
+
+fn received(receiver: &mut mpsc::Receiver<postmaster::Message>) -> usize {
+    let mut count = 0;
+    while receiver.try_recv().is_ok() {
+        count += 1;
+    }
+    count
+}
+
+#[tokio::test]
+async fn a_broadcast_reaches_every_registered_address_but_the_sender() {
+    let _isolation = postmaster::isolate();
+    let [mut main, mut alpha, mut beta, mut gamma] = register_all().await;
+
+    let report = postmaster::broadcast(Addresses::Main, Payloads::Note).await;
+    assert_eq!(
+        recipients(&report),
+        [Addresses::Alpha, Addresses::Beta, Addresses::Gamma]
+    );
+    assert!(report.is_success());
+    assert_eq!(received(&mut main), 0);
+    for receiver in [&mut alpha, &mut beta, &mut gamma] {
+        assert_eq!(received(receiver), 1);
+    }
+}
+
+#[tokio::test]
+async fn a_multicast_reports_the_outcome_for_each_destination() {
+    let _isolation = postmaster::isolate();
+    let [_main, mut alpha, mut beta, _gamma] = register_all().await;
+
+    let report = postmaster::multicast(
+        &[Addresses::Alpha, Addresses::Delta],
+        Addresses::Main,
+        Payloads::Note,
+    )
+    .await;
+    assert_eq!(recipients(&report), [Addresses::Alpha, Addresses::Delta]);
+    assert_eq!(report.result(Addresses::Alpha), Some(&Ok(())));
+    assert_eq!(
+        report.result(Addresses::Delta),
+        Some(&Err(PostmasterError::NoRecipient))
+    );
+    assert_eq!(report.result(Addresses::Beta), None);
+    assert_eq!((report.delivered(), report.failed()), (1, 1));
+    assert!(!report.is_success());
+    assert_eq!(received(&mut alpha), 1);
+    assert_eq!(received(&mut beta), 0);
+}
+
+#[tokio::test]
+async fn a_group_can_be_multicast_to() {
+    let _isolation = postmaster::isolate();
+    let [_main, mut alpha, mut beta, mut gamma] = register_all().await;
+
+    let report =
+        postmaster::multicast(postmaster::groups::ODD, Addresses::Main, Payloads::Note).await;
+    assert_eq!(recipients(&report), [Addresses::Alpha, Addresses::Gamma]);
+    assert!(report.is_success());
+    assert_eq!([&mut alpha, &mut beta, &mut gamma].map(received), [1, 0, 1]);
+}
+
+#[tokio::test]
+async fn only_registered_subscribers_other_than_the_publisher_receive_a_topic() {
+    let _isolation = postmaster::isolate();
+    let [mut main, mut alpha, mut beta, mut gamma] = register_all().await;
+    for address in [
+        Addresses::Main,
+        Addresses::Alpha,
+        Addresses::Gamma,
+        Addresses::Delta,
+    ] {
+        postmaster::subscribe(address, Topics::Weather);
+    }
+    postmaster::subscribe(Addresses::Beta, Topics::News);
+    postmaster::unsubscribe(Addresses::Gamma, Topics::Weather);
+    assert!(!postmaster::is_subscribed(
+        Addresses::Gamma,
+        Topics::Weather
+    ));
+
+    let report = postmaster::publish(Topics::Weather, Addresses::Main, Payloads::Note).await;
+    assert_eq!(recipients(&report), [Addresses::Alpha]);
+    assert!(report.is_success());
+    assert_eq!(
+        [&mut main, &mut alpha, &mut beta, &mut gamma].map(received),
+        [0, 1, 0, 0]
+    );
+}
+
+#[tokio::test]
+async fn resetting_the_postmaster_clears_subscriptions() {
+    let _isolation = postmaster::isolate();
+    postmaster::subscribe(Addresses::Alpha, Topics::News);
+
+    postmaster::reset();
+    assert!(!postmaster::is_subscribed(Addresses::Alpha, Topics::News));
+}
@@ -0,0 +1,250 @@
+use std::time::Duration;
+
+use post_haste::agent::{Agent, Inbox};
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+use tokio::time::Instant;
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Ping,
+    Pong,
+    Note(u8),
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Addresses {
+    Ponger,
+    Main,
+    Other,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+/// Replies to each ping with a pong, after waiting for the delay it is configured with.
+struct Ponger {
+    address: Addresses,
+    delay: Duration,
+}
+
+impl Agent for Ponger {
+    type Address = Addresses;
+    type Message = postmaster::Message;
+    type Config = Duration;
+
+    async fn create(address: Self::Address, delay: Self::Config) -> Self {
+        Self { address, delay }
+    }
+
+    async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+        loop {
+            let message = inbox.recv().await.unwrap();
+            if let Payloads::Ping = message.payload {
+                tokio::time::sleep(self.delay).await;
+                postmaster::send(message.source, self.address, Payloads::Pong)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+}
+
+fn is_pong(message: &postmaster::Message) -> bool {
+    matches!(message.payload, Payloads::Pong)
+}
+
+#[tokio::test(start_paused = true)]
+async fn injected_messages_reach_the_agent_and_replies_reach_the_mock() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    postmaster::register_agent!(Ponger, Ponger, Duration::ZERO).unwrap();
+
+    harness
+        .inject(Addresses::Ponger, Addresses::Main, Payloads::Ping)
+        .await
+        .unwrap();
+    let reply = harness
+        .expect_message(Addresses::Main, Duration::from_secs(1), is_pong)
+        .await;
+    assert!(matches!(reply.source, Addresses::Ponger));
+}
+
+#[tokio::test(start_paused = true)]
+async fn mocking_an_address_twice_fails() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness
+        .mock_all(&[Addresses::Main, Addresses::Other])
+        .await
+        .unwrap();
+    assert_eq!(
+        harness.mock(Addresses::Main).await,
+        Err(PostmasterError::AddressAlreadyTaken)
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn unmatched_messages_are_left_for_later_assertions() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+
+    for note in 0..3 {
+        harness
+            .inject(Addresses::Main, Addresses::Other, Payloads::Note(note))
+            .await
+            .unwrap();
+    }
+    let second = harness
+        .expect_message(Addresses::Main, Duration::ZERO, |message| {
+            matches!(message.payload, Payloads::Note(1))
+        })
+        .await;
+    assert!(matches!(second.payload, Payloads::Note(1)));
+
+    let remaining: Vec<_> = harness
+        .take_captured(Addresses::Main)
+        .into_iter()
+        .map(|message| message.payload)
+        .collect();
+    assert!(matches!(
+        remaining[..],
+        [Payloads::Note(0), Payloads::Note(2)]
+    ));
+    harness
+        .expect_no_message(Addresses::Main, Duration::from_secs(1))
+        .await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn windows_are_measured_in_virtual_time() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    postmaster::register_agent!(Ponger, Ponger, Duration::from_secs(90)).unwrap();
+
+    let start = Instant::now();
+    harness
+        .inject(Addresses::Ponger, Addresses::Main, Payloads::Ping)
+        .await
+        .unwrap();
+    harness
+        .expect_no_message(Addresses::Main, Duration::from_secs(60))
+        .await;
+    assert_eq!(start.elapsed(), Duration::from_secs(60));
+
+    harness
+        .expect_message(Addresses::Main, Duration::from_secs(60), is_pong)
+        .await;
+    assert_eq!(start.elapsed(), Duration::from_secs(90));
+}
+
+#[tokio::test(start_paused = true)]
+#[should_panic(expected = "no matching message arrived at Main")]
+async fn expect_message_panics_when_nothing_matches() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    harness
+        .inject(Addresses::Main, Addresses::Other, Payloads::Ping)
+        .await
+        .unwrap();
+    harness
+        .expect_message(Addresses::Main, Duration::from_secs(5), is_pong)
+        .await;
+}
+
+#[tokio::test(start_paused = true)]
+#[should_panic(expected = "1 unexpected messages arrived at Main")]
+async fn expect_no_message_panics_when_a_message_arrives() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    postmaster::message(Addresses::Main, Addresses::Other, Payloads::Ping)
+        .with_delay(Duration::from_secs(30))
+        .send()
+        .await
+        .unwrap();
+    harness
+        .expect_no_message(Addresses::Main, Duration::from_secs(60))
+        .await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn delayed_messages_arrive_after_their_delay_in_virtual_time() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+
+    let start = Instant::now();
+    postmaster::message(Addresses::Main, Addresses::Other, Payloads::Ping)
+        .with_delay(Duration::from_secs(3600))
+        .send()
+        .await
+        .unwrap();
+    harness
+        .expect_no_message(Addresses::Main, Duration::from_secs(3599))
+        .await;
+    harness
+        .expect_message(Addresses::Main, Duration::from_secs(2), |_| true)
+        .await;
+    assert_eq!(start.elapsed(), Duration::from_secs(3600));
+}
+
+#[tokio::test(start_paused = true)]
+async fn send_timeouts_expire_in_virtual_time() {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    postmaster::set_timeout(10_000_000);
+
+    // Fill the mock's mailbox, which is never emptied as nothing is waiting on it
+    let mut sent = 0;
+    while postmaster::try_send(Addresses::Main, Addresses::Other, Payloads::Ping).is_ok() {
+        sent += 1;
+    }
+    assert!(sent > 0);
+
+    let start = Instant::now();
+    assert_eq!(
+        postmaster::send(Addresses::Main, Addresses::Other, Payloads::Ping).await,
+        Err(PostmasterError::Timeout)
+    );
+    assert_eq!(start.elapsed(), Duration::from_secs(10));
+}
+
+#[tokio::test]
+async fn reset_deregisters_every_address() {
+    let _isolation = postmaster::isolate();
+    postmaster::register_agent!(Ponger, Ponger, Duration::ZERO).unwrap();
+    assert!(postmaster::is_registered(Addresses::Ponger));
+
+    postmaster::reset();
+    assert!(!postmaster::is_registered(Addresses::Ponger));
+    postmaster::register_agent!(Ponger, Ponger, Duration::ZERO).unwrap();
+}
+
+#[tokio::test]
+async fn isolation_resets_the_postmaster_when_it_ends() {
+    {
+        let mut harness = postmaster::harness::Harness::new();
+        harness.mock(Addresses::Main).await.unwrap();
+    }
+    let _isolation = postmaster::isolate();
+    assert!(!postmaster::is_registered(Addresses::Main));
+}
+
+#[cfg(feature = "test-harness")]
+#[tokio::test]
+async fn the_simulated_clock_fires_timers_as_it_advances() {
+    let clock = post_haste::harness::SimulatedClock::start();
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+
+    postmaster::message(Addresses::Main, Addresses::Other, Payloads::Ping)
+        .with_delay(Duration::from_secs(10))
+        .send()
+        .await
+        .unwrap();
+    clock.advance(Duration::from_secs(9)).await;
+    assert!(harness.take_captured(Addresses::Main).is_empty());
+
+    clock.advance(Duration::from_secs(1)).await;
+    // The timer has fired, but the task delivering the message may not have run yet
+    harness
+        .expect_message(Addresses::Main, Duration::from_millis(1), |_| true)
+        .await;
+    assert!(clock.elapsed() <= Duration::from_millis(10_001));
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "latency-histograms")]
+
+use std::time::Duration;
+
+use post_haste::agent::{Agent, Inbox};
+use post_haste::{PostHasteAddress, init_postmaster};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Work,
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Addresses {
+    Worker,
+    Main,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+const HANDLING_TIME: Duration = Duration::from_millis(50);
+
+/// Spends `HANDLING_TIME` on each piece of work, then tells `Main` that it is done.
+struct Worker;
+
+impl Agent for Worker {
+    type Address = Addresses;
+    type Message = postmaster::Message;
+    type Config = ();
+
+    async fn create(_address: Self::Address, _config: Self::Config) -> Self {
+        Self
+    }
+
+    async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+        loop {
+            let message = inbox.recv().await.unwrap();
+            tokio::time::sleep(HANDLING_TIME).await;
+            inbox.handled();
+            postmaster::send(message.source, Addresses::Worker, Payloads::Done)
+                .await
+                .unwrap();
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn queueing_and_handling_times_are_recorded_for_each_message() {
+    let _isolation = postmaster::isolate();
+    let (sender, mut receiver) = mpsc::channel(4);
+    postmaster::register(Addresses::Main, sender).await.unwrap();
+    postmaster::register_agent!(Worker, Worker, (), 2).unwrap();
+
+    // The second piece of work waits in the queue while the first is handled
+    for _ in 0..2 {
+        postmaster::send(Addresses::Worker, Addresses::Main, Payloads::Work)
+            .await
+            .unwrap();
+    }
+    for _ in 0..2 {
+        receiver.recv().await.unwrap();
+    }
+
+    let diagnostics = postmaster::get_address_diagnostics(Addresses::Worker);
+    // Both durations in the bucket from 10 ms to 100 ms
+    assert_eq!(diagnostics.handling_time.counts, [0, 0, 0, 0, 2, 0, 0]);
+    assert_eq!(diagnostics.queueing_latency.counts, [1, 0, 0, 0, 1, 0, 0]);
+}
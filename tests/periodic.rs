@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+use tokio::sync::mpsc::channel;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Tick,
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Addresses {
+    Receiver,
+    Sender,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+#[tokio::test(start_paused = true)]
+async fn zero_period_is_rejected() {
+    let _isolation = postmaster::isolate();
+    let (sender, _receiver) = channel(4);
+    postmaster::register(Addresses::Receiver, sender)
+        .await
+        .unwrap();
+
+    for period in [Duration::ZERO, Duration::from_nanos(999)] {
+        let result = postmaster::message(Addresses::Receiver, Addresses::Sender, Payloads::Tick)
+            .with_period(period, Payloads::clone)
+            .send()
+            .await;
+        assert_eq!(result.err(), Some(PostmasterError::ZeroPeriod));
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn ticks_are_delivered_each_period() {
+    let _isolation = postmaster::isolate();
+    let (sender, mut receiver) = channel(4);
+    postmaster::register(Addresses::Receiver, sender)
+        .await
+        .unwrap();
+
+    let handle = postmaster::message(Addresses::Receiver, Addresses::Sender, Payloads::Tick)
+        .with_period(Duration::from_millis(10), Payloads::clone)
+        .send()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(35)).await;
+    handle.stop();
+
+    let mut ticks = 0;
+    while receiver.try_recv().is_ok() {
+        ticks += 1;
+    }
+    assert_eq!(ticks, 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn the_schedule_starts_when_the_message_is_sent() {
+    let _isolation = postmaster::isolate();
+    let (sender, mut receiver) = channel(4);
+    postmaster::register(Addresses::Receiver, sender)
+        .await
+        .unwrap();
+    let start = Instant::now();
+
+    let handle = postmaster::message(Addresses::Receiver, Addresses::Sender, Payloads::Tick)
+        .with_period(Duration::from_secs(10), Payloads::clone)
+        .send()
+        .await
+        .unwrap();
+    // The clock moves on before the task delivering the ticks first runs
+    tokio::time::advance(Duration::from_secs(4)).await;
+    receiver.recv().await.unwrap();
+    handle.stop();
+    assert_eq!(start.elapsed(), Duration::from_secs(10));
+}
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use post_haste::agent::{Agent, Inbox};
+use post_haste::mailbox::Priority;
+use post_haste::{PostHasteAddress, init_postmaster};
+use tokio::sync::{Notify, mpsc};
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Note(u8),
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Addresses {
+    Gated,
+    Main,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+/// Waits to be let through the gate before receiving anything, then passes each note on to `Main`.
+struct Gated {
+    gate: Arc<Notify>,
+}
+
+impl Agent for Gated {
+    type Address = Addresses;
+    type Message = postmaster::Message;
+    type Config = Arc<Notify>;
+
+    async fn create(_address: Self::Address, gate: Self::Config) -> Self {
+        Self { gate }
+    }
+
+    async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+        self.gate.notified().await;
+        loop {
+            let message = inbox.recv().await.unwrap();
+            postmaster::send(Addresses::Main, Addresses::Gated, message.payload)
+                .await
+                .unwrap();
+        }
+    }
+}
+
+/// Send the notes to `Gated` with the given priorities while it is held at the gate, then return the order in which it received them.
+async fn received_order(gate: Arc<Notify>, notes: &[(u8, Priority)]) -> Vec<u8> {
+    let (sender, mut receiver) = mpsc::channel(8);
+    postmaster::register(Addresses::Main, sender).await.unwrap();
+    for &(note, priority) in notes {
+        postmaster::message(Addresses::Gated, Addresses::Main, Payloads::Note(note))
+            .with_priority(priority)
+            .send()
+            .await
+            .unwrap();
+    }
+    gate.notify_one();
+
+    let mut order = Vec::new();
+    for _ in notes {
+        let message = tokio::time::timeout(Duration::from_secs(1), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let Payloads::Note(note) = message.payload;
+        order.push(note);
+    }
+    order
+}
+
+#[tokio::test]
+async fn a_later_message_with_a_higher_priority_is_received_first() {
+    let _isolation = postmaster::isolate();
+    let gate = Arc::new(Notify::new());
+    postmaster::register_agent!(Gated, Gated, gate.clone(), priority_levels: 3, capacity: 4)
+        .unwrap();
+
+    let order = received_order(
+        gate,
+        &[
+            (0, Priority::NORMAL),
+            (1, Priority(1)),
+            (2, Priority::NORMAL),
+            (3, Priority(2)),
+        ],
+    )
+    .await;
+    assert_eq!(order, [3, 1, 0, 2]);
+}
+
+#[tokio::test]
+async fn priorities_beyond_the_mailbox_levels_are_treated_as_its_highest() {
+    let _isolation = postmaster::isolate();
+    let gate = Arc::new(Notify::new());
+    postmaster::register_agent!(Gated, Gated, gate.clone(), priority_levels: 2, capacity: 4)
+        .unwrap();
+
+    let order = received_order(
+        gate,
+        &[(0, Priority::NORMAL), (1, Priority(200)), (2, Priority(1))],
+    )
+    .await;
+    assert_eq!(order, [1, 2, 0]);
+}
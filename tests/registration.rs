@@ -0,0 +1,120 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use post_haste::agent::{Agent, Inbox};
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Note(u8),
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Addresses {
+    Worker,
+    Main,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+/// Does nothing but wait, noting when it is dropped.
+struct Worker {
+    dropped: Arc<AtomicBool>,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Agent for Worker {
+    type Address = Addresses;
+    type Message = postmaster::Message;
+    type Config = Arc<AtomicBool>;
+
+    async fn create(_address: Self::Address, dropped: Self::Config) -> Self {
+        Self { dropped }
+    }
+
+    async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+        loop {
+            let _ = inbox.recv().await;
+        }
+    }
+}
+
+#[tokio::test]
+async fn a_deregistered_address_rejects_messages_until_it_is_registered_again() {
+    let _isolation = postmaster::isolate();
+    let (sender, _receiver) = mpsc::channel(4);
+    postmaster::register(Addresses::Main, sender).await.unwrap();
+    postmaster::send(Addresses::Main, Addresses::Worker, Payloads::Note(0))
+        .await
+        .unwrap();
+
+    postmaster::deregister(Addresses::Main).unwrap();
+    assert!(!postmaster::is_registered(Addresses::Main));
+    assert_eq!(
+        postmaster::send(Addresses::Main, Addresses::Worker, Payloads::Note(1)).await,
+        Err(PostmasterError::NoRecipient)
+    );
+    assert_eq!(
+        postmaster::deregister(Addresses::Main),
+        Err(PostmasterError::NoRecipient)
+    );
+
+    let (sender, mut receiver) = mpsc::channel(4);
+    postmaster::register(Addresses::Main, sender).await.unwrap();
+    postmaster::send(Addresses::Main, Addresses::Worker, Payloads::Note(2))
+        .await
+        .unwrap();
+    assert!(matches!(
+        receiver.recv().await.unwrap().payload,
+        Payloads::Note(2)
+    ));
+}
+
+#[tokio::test]
+async fn deregistering_and_draining_returns_the_queued_messages() {
+    let _isolation = postmaster::isolate();
+    let (sender, receiver) = mpsc::channel(4);
+    postmaster::register(Addresses::Main, sender).await.unwrap();
+    for note in 0..3 {
+        postmaster::send(Addresses::Main, Addresses::Worker, Payloads::Note(note))
+            .await
+            .unwrap();
+    }
+
+    let mut inbox = Inbox::from(receiver);
+    let drained: Vec<_> = postmaster::deregister_and_drain(Addresses::Main, &mut inbox)
+        .unwrap()
+        .map(|message| message.payload)
+        .collect();
+    assert!(matches!(
+        drained[..],
+        [Payloads::Note(0), Payloads::Note(1), Payloads::Note(2)]
+    ));
+    assert!(!postmaster::is_registered(Addresses::Main));
+}
+
+#[tokio::test]
+async fn deregistering_an_agent_stops_its_task() {
+    let _isolation = postmaster::isolate();
+    let dropped = Arc::new(AtomicBool::new(false));
+    postmaster::register_agent!(Worker, Worker, dropped.clone()).unwrap();
+
+    postmaster::deregister(Addresses::Worker).unwrap();
+    tokio::time::timeout(Duration::from_secs(1), async {
+        while !dropped.load(Ordering::SeqCst) {
+            tokio::task::yield_now().await;
+        }
+    })
+    .await
+    .expect("the Agent's task was left running");
+
+    // The address can be taken by a new Agent
+    postmaster::register_agent!(Worker, Worker, Arc::new(AtomicBool::new(false))).unwrap();
+}
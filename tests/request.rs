@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Payloads {
+    Ping,
+    Pong,
+    Note(u8),
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Addresses {
+    Server,
+    Client,
+    Bystander,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+/// Register a plain inbox at the given address.
+async fn register(address: Addresses) -> mpsc::Receiver<postmaster::Message> {
+    let (sender, receiver) = mpsc::channel(4);
+    postmaster::register(address, sender).await.unwrap();
+    receiver
+}
+
+#[tokio::test]
+async fn a_request_resolves_to_the_reply() {
+    let _isolation = postmaster::isolate();
+    let mut server = register(Addresses::Server).await;
+
+    let request = tokio::spawn(postmaster::request(
+        Addresses::Server,
+        Addresses::Client,
+        Payloads::Ping,
+    ));
+    let message = server.recv().await.unwrap();
+    assert_eq!(message.payload, Payloads::Ping);
+    message.reply_to.unwrap().reply(Payloads::Pong).unwrap();
+
+    assert_eq!(request.await.unwrap(), Ok(Payloads::Pong));
+}
+
+#[tokio::test]
+async fn a_reply_handle_dropped_without_replying_fails_the_request() {
+    let _isolation = postmaster::isolate();
+    let mut server = register(Addresses::Server).await;
+
+    let request = tokio::spawn(postmaster::request(
+        Addresses::Server,
+        Addresses::Client,
+        Payloads::Ping,
+    ));
+    drop(server.recv().await.unwrap());
+
+    assert_eq!(request.await.unwrap(), Err(PostmasterError::ReplyDropped));
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_request_times_out_and_a_late_reply_finds_the_requester_gone() {
+    let _isolation = postmaster::isolate();
+    let mut server = register(Addresses::Server).await;
+
+    let request = tokio::spawn(
+        postmaster::message(Addresses::Server, Addresses::Client, Payloads::Ping)
+            .with_timeout(Duration::from_millis(10))
+            .expect_reply(),
+    );
+    let handle = server.recv().await.unwrap().reply_to.unwrap();
+
+    assert_eq!(request.await.unwrap(), Err(PostmasterError::Timeout));
+    assert_eq!(
+        handle.reply(Payloads::Pong),
+        Err(PostmasterError::RequesterGone)
+    );
+}
+
+#[tokio::test]
+async fn unrelated_messages_do_not_disturb_a_request() {
+    let _isolation = postmaster::isolate();
+    let mut server = register(Addresses::Server).await;
+    let mut client = register(Addresses::Client).await;
+
+    postmaster::send(Addresses::Server, Addresses::Bystander, Payloads::Note(1))
+        .await
+        .unwrap();
+    let request = tokio::spawn(postmaster::request(
+        Addresses::Server,
+        Addresses::Client,
+        Payloads::Ping,
+    ));
+
+    let unrelated = server.recv().await.unwrap();
+    assert_eq!(unrelated.payload, Payloads::Note(1));
+    assert!(unrelated.reply_to.is_none());
+    postmaster::send(Addresses::Client, Addresses::Server, Payloads::Note(2))
+        .await
+        .unwrap();
+
+    let message = server.recv().await.unwrap();
+    assert_eq!(message.payload, Payloads::Ping);
+    message.reply_to.unwrap().reply(Payloads::Pong).unwrap();
+
+    assert_eq!(request.await.unwrap(), Ok(Payloads::Pong));
+    // The reply went straight to the requester, leaving only the unrelated note in its inbox
+    assert_eq!(client.recv().await.unwrap().payload, Payloads::Note(2));
+    assert!(client.try_recv().is_err());
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use post_haste::agent::{Agent, Delivery, Inbox};
+use post_haste::handler::{Context, MessageHandler};
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Work,
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Addresses {
+    Unwrapper,
+    Handler,
+    Listener,
+    Panicker,
+    Main,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+/// Receives in the style of the examples, unwrapping every message.
+struct Unwrapper;
+
+impl Agent for Unwrapper {
+    type Address = Addresses;
+    type Message = postmaster::Message;
+    type Config = ();
+
+    async fn create(_address: Self::Address, _config: Self::Config) -> Self {
+        Self
+    }
+
+    async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+        loop {
+            let _message = inbox.recv().await.unwrap();
+        }
+    }
+}
+
+/// Counts the messages it handles, and reports the count when it shuts down.
+struct Counter {
+    handled: usize,
+    report: Arc<AtomicUsize>,
+}
+
+impl MessageHandler for Counter {
+    type Address = Addresses;
+    type Config = Arc<AtomicUsize>;
+    type Message = postmaster::Message;
+    type Received = postmaster::Message;
+
+    async fn create(_address: Self::Address, report: Self::Config) -> Self {
+        Self { handled: 0, report }
+    }
+
+    async fn handle(&mut self, _ctx: &mut Context<Self::Address>, _message: Self::Message) {
+        self.handled += 1;
+    }
+
+    async fn on_shutdown(&mut self, _ctx: &mut Context<Self::Address>) {
+        self.report.store(self.handled, Ordering::Relaxed);
+    }
+}
+
+/// Records whether it was notified of the shutdown.
+struct Listener;
+
+impl Agent for Listener {
+    type Address = Addresses;
+    type Message = postmaster::Message;
+    type Config = Arc<AtomicUsize>;
+
+    async fn create(_address: Self::Address, _config: Self::Config) -> Self {
+        Self
+    }
+
+    async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+        loop {
+            if let Delivery::Shutdown = inbox.next().await {
+                NOTIFIED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+static NOTIFIED: AtomicUsize = AtomicUsize::new(0);
+
+struct Panicker;
+
+impl Agent for Panicker {
+    type Address = Addresses;
+    type Message = postmaster::Message;
+    type Config = ();
+
+    async fn create(_address: Self::Address, _config: Self::Config) -> Self {
+        Self
+    }
+
+    async fn run(self, _inbox: Inbox<Self::Message>) -> ! {
+        panic!("the Agent failed")
+    }
+}
+
+#[tokio::test]
+async fn agents_which_unwrap_every_message_shut_down_promptly() {
+    let _isolation = postmaster::isolate();
+    postmaster::register_agent!(Unwrapper, Unwrapper, ()).unwrap();
+    postmaster::send(Addresses::Unwrapper, Addresses::Main, Payloads::Work)
+        .await
+        .unwrap();
+
+    let started = tokio::time::Instant::now();
+    assert_eq!(postmaster::shutdown(Duration::from_secs(2)).await, Ok(()));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn handlers_drain_their_inbox_then_shut_down_with_their_state() {
+    let _isolation = postmaster::isolate();
+    let report = Arc::new(AtomicUsize::new(0));
+    postmaster::register_agent!(Handler, Counter, report.clone(), 4).unwrap();
+    for _ in 0..3 {
+        postmaster::send(Addresses::Handler, Addresses::Main, Payloads::Work)
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(postmaster::shutdown(Duration::from_secs(2)).await, Ok(()));
+    assert_eq!(report.load(Ordering::Relaxed), 3);
+    assert_eq!(
+        postmaster::send(Addresses::Handler, Addresses::Main, Payloads::Work).await,
+        Err(PostmasterError::ShuttingDown)
+    );
+}
+
+#[tokio::test]
+async fn agents_are_notified_of_the_shutdown_once() {
+    let _isolation = postmaster::isolate();
+    NOTIFIED.store(0, Ordering::Relaxed);
+    postmaster::register_agent!(Listener, Listener, Arc::default()).unwrap();
+
+    assert_eq!(postmaster::shutdown(Duration::from_secs(2)).await, Ok(()));
+    assert_eq!(NOTIFIED.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn agents_which_have_already_failed_do_not_hold_up_the_shutdown() {
+    let _isolation = postmaster::isolate();
+    postmaster::register_agent!(Panicker, Panicker, ()).unwrap();
+    tokio::task::yield_now().await;
+
+    let started = tokio::time::Instant::now();
+    assert_eq!(postmaster::shutdown(Duration::from_secs(2)).await, Ok(()));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
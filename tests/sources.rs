@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use post_haste::agent::{Agent, Inbox};
+use post_haste::handler::{Context, MessageHandler};
+use post_haste::mailbox::Mailbox;
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Ping,
+    Pong,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PostHasteAddress)]
+enum Addresses {
+    Ponger,
+    Handler,
+    Retiree,
+    Main,
+}
+
+init_postmaster!(Addresses, Payloads, sources: authenticated);
+
+/// Replies to each ping through the context made from its inbox.
+struct Ponger;
+
+impl Agent for Ponger {
+    type Address = Addresses;
+    type Message = postmaster::Message;
+    type Config = ();
+
+    async fn create(_address: Self::Address, _config: Self::Config) -> Self {
+        Self
+    }
+
+    async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+        let ctx = postmaster::AgentContext::from_inbox(&inbox).unwrap();
+        loop {
+            let message = inbox.recv().await.unwrap();
+            ctx.reply(&message, Payloads::Pong).await.unwrap();
+        }
+    }
+}
+
+/// Replies to each ping through the context it is handed.
+struct Handler;
+
+impl MessageHandler for Handler {
+    type Address = Addresses;
+    type Config = ();
+    type Message = postmaster::Message;
+    type Received = postmaster::Message;
+
+    async fn create(_address: Self::Address, _config: Self::Config) -> Self {
+        Self
+    }
+
+    async fn handle(&mut self, ctx: &mut Context<Self::Address>, message: Self::Received) {
+        ctx.reply(&message, Payloads::Pong).await.unwrap();
+    }
+}
+
+/// What `Retiree` saw when it used its second token after retiring with its first.
+type StaleOutcomes = (
+    Result<(), PostmasterError>,
+    Result<(), PostmasterError>,
+    bool,
+);
+
+/// Retires from its address when pinged, then tries to carry on with a second token for the same registration.
+struct Retiree {
+    report: mpsc::Sender<StaleOutcomes>,
+}
+
+impl Agent for Retiree {
+    type Address = Addresses;
+    type Message = postmaster::Message;
+    type Config = mpsc::Sender<StaleOutcomes>;
+
+    async fn create(_address: Self::Address, report: Self::Config) -> Self {
+        Self { report }
+    }
+
+    async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+        let first = postmaster::AgentContext::from_inbox(&inbox).unwrap();
+        let second = postmaster::AgentContext::from_inbox(&inbox).unwrap();
+        inbox.recv().await.unwrap();
+        postmaster::deregister(first.into_source()).unwrap();
+
+        // Someone else takes the address over
+        let (sender, _receiver) = mpsc::channel(1);
+        let _token = postmaster::register(Addresses::Retiree, sender)
+            .await
+            .unwrap();
+        let sent = second.send_to(Addresses::Main, Payloads::Pong).await;
+        let deregistered = postmaster::deregister(second.into_source());
+        let still_registered = postmaster::is_registered(Addresses::Retiree);
+        self.report
+            .send((sent, deregistered, still_registered))
+            .await
+            .unwrap();
+        core::future::pending().await
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn registered_agents_send_from_their_own_address() {
+    let _isolation = postmaster::isolate();
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(2);
+    let token = postmaster::register(Addresses::Main, sender).await.unwrap();
+    postmaster::register_agent!(Ponger, Ponger, ()).unwrap();
+    postmaster::register_agent!(Handler, Handler, ()).unwrap();
+
+    for agent in [Addresses::Ponger, Addresses::Handler] {
+        postmaster::send(agent, &token, Payloads::Ping)
+            .await
+            .unwrap();
+        let reply = tokio::time::timeout(Duration::from_secs(1), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(reply.payload, Payloads::Pong));
+        assert_eq!(reply.source, agent);
+    }
+}
+
+#[test]
+fn a_context_cannot_be_made_from_an_unregistered_inbox() {
+    let (_mailbox, inbox) = Mailbox::<postmaster::Message>::prioritised(1, 1);
+    assert!(postmaster::AgentContext::from_inbox(&inbox).is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn tokens_go_stale_when_their_address_is_deregistered() {
+    let _isolation = postmaster::isolate();
+    let (report, mut outcomes) = mpsc::channel(1);
+    postmaster::register_agent!(Retiree, Retiree, report).unwrap();
+    let (sender, _receiver) = mpsc::channel(1);
+    let token = postmaster::register(Addresses::Main, sender).await.unwrap();
+
+    postmaster::send(Addresses::Retiree, &token, Payloads::Ping)
+        .await
+        .unwrap();
+    let (sent, deregistered, still_registered) = outcomes.recv().await.unwrap();
+    assert_eq!(sent, Err(PostmasterError::StaleSource));
+    assert_eq!(deregistered, Err(PostmasterError::StaleSource));
+    // The stale token could not remove the new registration
+    assert!(still_registered);
+}
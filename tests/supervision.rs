@@ -0,0 +1,204 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use post_haste::agent::{Agent, Inbox};
+use post_haste::supervision::{RestartLimit, RestartStrategy, SupervisionEvent};
+use post_haste::{PostHasteAddress, init_postmaster};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    /// Makes the Agent panic, after a pause in which more messages can be forwarded to it
+    Crash,
+    Note(u8),
+    Seen(u8),
+    Supervision(SupervisionEvent<Addresses>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PostHasteAddress)]
+enum Addresses {
+    First,
+    Second,
+    Main,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+/// Passes each note on to `Main`, counting how many times it has been created.
+struct Fragile {
+    address: Addresses,
+}
+
+impl Agent for Fragile {
+    type Address = Addresses;
+    type Message = postmaster::Message;
+    type Config = Arc<AtomicU32>;
+
+    async fn create(address: Self::Address, created: Self::Config) -> Self {
+        created.fetch_add(1, Ordering::SeqCst);
+        Self { address }
+    }
+
+    async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+        loop {
+            match inbox.recv().await.unwrap().payload {
+                Payloads::Crash => {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    panic!("crashed on purpose");
+                }
+                Payloads::Note(note) => {
+                    postmaster::send(Addresses::Main, self.address, Payloads::Seen(note))
+                        .await
+                        .unwrap();
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Supervise `Fragile` at both addresses, reporting to a mailbox at `Main`.
+async fn start(
+    strategy: RestartStrategy,
+    limit: RestartLimit,
+) -> ([Arc<AtomicU32>; 2], mpsc::Receiver<postmaster::Message>) {
+    postmaster::set_supervision(strategy, limit);
+    postmaster::report_supervision_events(Addresses::Main, Payloads::Supervision);
+    let (sender, receiver) = mpsc::channel(16);
+    postmaster::register(Addresses::Main, sender).await.unwrap();
+    let created = [Arc::new(AtomicU32::new(0)), Arc::new(AtomicU32::new(0))];
+    postmaster::register_supervised_agent!(First, Fragile, created[0].clone(), 4).unwrap();
+    postmaster::register_supervised_agent!(Second, Fragile, created[1].clone(), 4).unwrap();
+    (created, receiver)
+}
+
+async fn next_payload(receiver: &mut mpsc::Receiver<postmaster::Message>) -> Payloads {
+    tokio::time::timeout(Duration::from_secs(1), receiver.recv())
+        .await
+        .expect("nothing arrived at Main")
+        .unwrap()
+        .payload
+}
+
+fn restarted(address: Addresses, restarts: u32) -> impl Fn(&Payloads) -> bool {
+    move |payload| matches!(payload, Payloads::Supervision(event) if *event == SupervisionEvent::Restarted { address, restarts })
+}
+
+#[tokio::test(start_paused = true)]
+async fn one_for_one_restarts_only_the_failed_agent() {
+    let _isolation = postmaster::isolate();
+    let (created, mut receiver) = start(RestartStrategy::OneForOne, RestartLimit::default()).await;
+
+    postmaster::send(Addresses::First, Addresses::Main, Payloads::Crash)
+        .await
+        .unwrap();
+    assert!(restarted(Addresses::First, 1)(
+        &next_payload(&mut receiver).await
+    ));
+    assert_eq!(created[0].load(Ordering::SeqCst), 2);
+    assert_eq!(created[1].load(Ordering::SeqCst), 1);
+
+    // The restarted Agent carries on at the same address
+    postmaster::send(Addresses::First, Addresses::Main, Payloads::Note(1))
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_payload(&mut receiver).await,
+        Payloads::Seen(1)
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn one_for_all_restarts_every_supervised_agent() {
+    let _isolation = postmaster::isolate();
+    let (created, mut receiver) = start(RestartStrategy::OneForAll, RestartLimit::default()).await;
+
+    postmaster::send(Addresses::First, Addresses::Main, Payloads::Crash)
+        .await
+        .unwrap();
+    let mut events = [
+        next_payload(&mut receiver).await,
+        next_payload(&mut receiver).await,
+    ];
+    events.sort_by_key(|payload| !restarted(Addresses::First, 1)(payload));
+    assert!(restarted(Addresses::First, 1)(&events[0]));
+    assert!(restarted(Addresses::Second, 1)(&events[1]));
+    assert_eq!(created[0].load(Ordering::SeqCst), 2);
+    assert_eq!(created[1].load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn the_supervisor_gives_up_once_the_restart_limit_is_exceeded() {
+    let _isolation = postmaster::isolate();
+    let limit = RestartLimit {
+        max_restarts: 2,
+        window: Duration::from_secs(60),
+    };
+    let (created, mut receiver) = start(RestartStrategy::OneForOne, limit).await;
+
+    for restarts in 1..=2 {
+        postmaster::send(Addresses::First, Addresses::Main, Payloads::Crash)
+            .await
+            .unwrap();
+        assert!(restarted(Addresses::First, restarts)(
+            &next_payload(&mut receiver).await
+        ));
+    }
+    postmaster::send(Addresses::First, Addresses::Main, Payloads::Crash)
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_payload(&mut receiver).await,
+        Payloads::Supervision(SupervisionEvent::GaveUp {
+            address: Addresses::First
+        })
+    ));
+    assert_eq!(created[0].load(Ordering::SeqCst), 3);
+    assert!(!postmaster::is_registered(Addresses::First));
+    assert!(postmaster::is_registered(Addresses::Second));
+}
+
+#[tokio::test(start_paused = true)]
+async fn restarts_outside_the_window_do_not_count_towards_the_limit() {
+    let _isolation = postmaster::isolate();
+    let limit = RestartLimit {
+        max_restarts: 1,
+        window: Duration::from_secs(60),
+    };
+    let (_created, mut receiver) = start(RestartStrategy::OneForOne, limit).await;
+
+    for _ in 0..3 {
+        postmaster::send(Addresses::First, Addresses::Main, Payloads::Crash)
+            .await
+            .unwrap();
+        assert!(restarted(Addresses::First, 1)(
+            &next_payload(&mut receiver).await
+        ));
+        tokio::time::sleep(Duration::from_secs(61)).await;
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn only_the_message_being_handled_is_lost_in_a_restart() {
+    let _isolation = postmaster::isolate();
+    let (_created, mut receiver) = start(RestartStrategy::OneForOne, RestartLimit::default()).await;
+
+    // While the Agent handles the crash, the first note is forwarded into its inbox and the second waits with the supervisor
+    for payload in [Payloads::Crash, Payloads::Note(1), Payloads::Note(2)] {
+        postmaster::send(Addresses::First, Addresses::Main, payload)
+            .await
+            .unwrap();
+    }
+    assert!(restarted(Addresses::First, 1)(
+        &next_payload(&mut receiver).await
+    ));
+    assert!(matches!(
+        next_payload(&mut receiver).await,
+        Payloads::Seen(1)
+    ));
+    assert!(matches!(
+        next_payload(&mut receiver).await,
+        Payloads::Seen(2)
+    ));
+}
@@ -0,0 +1,142 @@
+use std::sync::Mutex;
+
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Ping,
+    Pong,
+    Tap(postmaster::TapRecord),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PostHasteAddress)]
+enum Addresses {
+    Main,
+    Worker,
+    Unregistered,
+    Debug,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+fn describe(payload: &Payloads) -> &'static str {
+    match payload {
+        Payloads::Ping => "ping",
+        Payloads::Pong => "pong",
+        Payloads::Tap(_) => "tap",
+    }
+}
+
+/// What the tests check of each record: its addresses, description and outcome.
+type Summary = (
+    Addresses,
+    Addresses,
+    &'static str,
+    Result<(), PostmasterError>,
+);
+
+fn summarise(record: &postmaster::TapRecord) -> Summary {
+    (
+        record.source,
+        record.destination,
+        record.description,
+        record.outcome,
+    )
+}
+
+async fn register(address: Addresses) -> mpsc::Receiver<postmaster::Message> {
+    let (sender, receiver) = mpsc::channel(8);
+    postmaster::register(address, sender).await.unwrap();
+    receiver
+}
+
+/// Send a ping and a pong from `Main` and from `Worker` to each other and to the unregistered address.
+async fn send_traffic() {
+    for (source, destination) in [
+        (Addresses::Main, Addresses::Worker),
+        (Addresses::Main, Addresses::Unregistered),
+        (Addresses::Worker, Addresses::Main),
+        (Addresses::Worker, Addresses::Unregistered),
+    ] {
+        for payload in [Payloads::Ping, Payloads::Pong] {
+            let _ = postmaster::send(destination, source, payload).await;
+        }
+    }
+}
+
+#[tokio::test]
+async fn a_tap_forwards_records_of_the_messages_from_its_sources() {
+    let _isolation = postmaster::isolate();
+    let _main = register(Addresses::Main).await;
+    let _worker = register(Addresses::Worker).await;
+    let mut debug = register(Addresses::Debug).await;
+    postmaster::tap()
+        .source(Addresses::Main)
+        .describe_payload(describe)
+        .forward_to(Addresses::Debug, Payloads::Tap);
+
+    send_traffic().await;
+
+    let mut records = Vec::new();
+    while let Ok(message) = debug.try_recv() {
+        match message.payload {
+            Payloads::Tap(record) => records.push(summarise(&record)),
+            payload => panic!("expected a tap record, got {payload:?}"),
+        }
+    }
+    assert_eq!(
+        records,
+        [
+            (Addresses::Main, Addresses::Worker, "ping", Ok(())),
+            (Addresses::Main, Addresses::Worker, "pong", Ok(())),
+            (
+                Addresses::Main,
+                Addresses::Unregistered,
+                "ping",
+                Err(PostmasterError::NoRecipient)
+            ),
+            (
+                Addresses::Main,
+                Addresses::Unregistered,
+                "pong",
+                Err(PostmasterError::NoRecipient)
+            ),
+        ]
+    );
+}
+
+static OBSERVED: Mutex<Vec<Summary>> = Mutex::new(Vec::new());
+
+#[tokio::test]
+async fn a_tap_passes_records_of_the_messages_to_its_destinations_to_a_callback() {
+    let _isolation = postmaster::isolate();
+    let _main = register(Addresses::Main).await;
+    let _worker = register(Addresses::Worker).await;
+    postmaster::tap()
+        .source(Addresses::Worker)
+        .destination(Addresses::Unregistered)
+        .destination(Addresses::Debug)
+        .call(|record| OBSERVED.lock().unwrap().push(summarise(record)));
+
+    send_traffic().await;
+
+    // Without `describe_payload()` the description is left empty
+    assert_eq!(
+        OBSERVED.lock().unwrap()[..],
+        [
+            (
+                Addresses::Worker,
+                Addresses::Unregistered,
+                "",
+                Err(PostmasterError::NoRecipient)
+            ),
+            (
+                Addresses::Worker,
+                Addresses::Unregistered,
+                "",
+                Err(PostmasterError::NoRecipient)
+            ),
+        ]
+    );
+}
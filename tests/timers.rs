@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use post_haste::handler::{Context, MessageHandler, TIMER_SLOTS};
+use post_haste::{PostHasteAddress, PostmasterError, init_postmaster};
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Payloads {
+    Start { name: &'static str, tag: u8 },
+    Cancel(&'static str),
+    Ring(u8),
+    Started(Result<(), PostmasterError>),
+    Cancelled(bool),
+    Rang(u8),
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Addresses {
+    Timekeeper,
+    Main,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+const DELAY: Duration = Duration::from_secs(10);
+
+/// Starts and cancels named timers as `Main` asks, reporting back the result of each request and each timer which rings.
+struct Timekeeper;
+
+impl MessageHandler for Timekeeper {
+    type Address = Addresses;
+    type Config = ();
+    type Message = postmaster::Message;
+    type Received = postmaster::Message;
+
+    async fn create(_address: Self::Address, _config: Self::Config) -> Self {
+        Self
+    }
+
+    async fn handle(&mut self, ctx: &mut Context<Self::Address>, message: Self::Message) {
+        let report = match message.payload {
+            Payloads::Start { name, tag } => {
+                Payloads::Started(ctx.start_timer(name, DELAY, Payloads::Ring(tag)).await)
+            }
+            Payloads::Cancel(name) => Payloads::Cancelled(ctx.cancel_timer(name)),
+            Payloads::Ring(tag) => Payloads::Rang(tag),
+            _ => return,
+        };
+        ctx.send_to(Addresses::Main, report).await.unwrap();
+    }
+}
+
+/// Send a request to the `Timekeeper` and wait for its report.
+async fn ask(harness: &mut postmaster::harness::Harness, request: Payloads) -> Payloads {
+    harness
+        .inject(Addresses::Timekeeper, Addresses::Main, request)
+        .await
+        .unwrap();
+    harness
+        .expect_message(Addresses::Main, Duration::from_millis(1), |message| {
+            matches!(
+                message.payload,
+                Payloads::Started(_) | Payloads::Cancelled(_)
+            )
+        })
+        .await
+        .payload
+}
+
+async fn start(
+    harness: &mut postmaster::harness::Harness,
+    name: &'static str,
+    tag: u8,
+) -> Payloads {
+    ask(harness, Payloads::Start { name, tag }).await
+}
+
+async fn start_timekeeper() -> postmaster::harness::Harness {
+    let mut harness = postmaster::harness::Harness::new();
+    harness.mock(Addresses::Main).await.unwrap();
+    postmaster::register_agent!(Timekeeper, Timekeeper, ()).unwrap();
+    harness
+}
+
+fn rang(tag: u8) -> impl Fn(&postmaster::Message) -> bool {
+    move |message| message.payload == Payloads::Rang(tag)
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_timer_rings_once_its_delay_has_elapsed() {
+    let mut harness = start_timekeeper().await;
+    let started = Instant::now();
+
+    assert_eq!(
+        start(&mut harness, "alarm", 1).await,
+        Payloads::Started(Ok(()))
+    );
+    harness
+        .expect_no_message(Addresses::Main, DELAY - Duration::from_millis(1))
+        .await;
+    harness
+        .expect_message(Addresses::Main, Duration::from_millis(2), rang(1))
+        .await;
+    assert_eq!(started.elapsed(), DELAY);
+}
+
+#[tokio::test(start_paused = true)]
+async fn restarting_a_timer_replaces_the_pending_one() {
+    let mut harness = start_timekeeper().await;
+    let started = Instant::now();
+
+    assert_eq!(
+        start(&mut harness, "alarm", 1).await,
+        Payloads::Started(Ok(()))
+    );
+    harness.expect_no_message(Addresses::Main, DELAY / 2).await;
+    assert_eq!(
+        start(&mut harness, "alarm", 2).await,
+        Payloads::Started(Ok(()))
+    );
+
+    harness
+        .expect_message(Addresses::Main, DELAY * 2, rang(2))
+        .await;
+    assert_eq!(started.elapsed(), DELAY / 2 + DELAY);
+    harness.expect_no_message(Addresses::Main, DELAY * 2).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_cancelled_timer_never_rings() {
+    let mut harness = start_timekeeper().await;
+
+    assert_eq!(
+        start(&mut harness, "alarm", 1).await,
+        Payloads::Started(Ok(()))
+    );
+    assert_eq!(
+        ask(&mut harness, Payloads::Cancel("alarm")).await,
+        Payloads::Cancelled(true)
+    );
+    assert_eq!(
+        ask(&mut harness, Payloads::Cancel("alarm")).await,
+        Payloads::Cancelled(false)
+    );
+    harness.expect_no_message(Addresses::Main, DELAY * 2).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_timer_cannot_be_started_while_every_slot_is_pending() {
+    const NAMES: [&str; TIMER_SLOTS + 1] = ["first", "second", "third", "fourth", "fifth"];
+    let mut harness = start_timekeeper().await;
+
+    for (tag, name) in (0..).zip(&NAMES[..TIMER_SLOTS]) {
+        assert_eq!(
+            start(&mut harness, name, tag).await,
+            Payloads::Started(Ok(()))
+        );
+    }
+    assert_eq!(
+        start(&mut harness, NAMES[TIMER_SLOTS], 9).await,
+        Payloads::Started(Err(PostmasterError::TimerSlotsFull))
+    );
+    // Restarting a pending timer reuses its own slot
+    assert_eq!(
+        start(&mut harness, NAMES[0], 0).await,
+        Payloads::Started(Ok(()))
+    );
+
+    // Once the timers have rung, their slots are free again
+    for tag in 0..TIMER_SLOTS as u8 {
+        harness
+            .expect_message(Addresses::Main, DELAY * 2, rang(tag))
+            .await;
+    }
+    assert_eq!(
+        start(&mut harness, NAMES[TIMER_SLOTS], 9).await,
+        Payloads::Started(Ok(()))
+    );
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "tracing")]
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use post_haste::handler::{Context, MessageHandler};
+use post_haste::{PostHasteAddress, init_postmaster};
+
+#[derive(Debug, Clone)]
+enum Payloads {
+    Work,
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Addresses {
+    Handler,
+    Main,
+}
+
+init_postmaster!(Addresses, Payloads);
+
+/// Records the name of the span in which each message is handled.
+struct Recorder {
+    spans: Arc<Mutex<Vec<Option<&'static str>>>>,
+}
+
+impl MessageHandler for Recorder {
+    type Address = Addresses;
+    type Config = Arc<Mutex<Vec<Option<&'static str>>>>;
+    type Message = postmaster::Message;
+    type Received = postmaster::Message;
+
+    async fn create(_address: Self::Address, spans: Self::Config) -> Self {
+        Self { spans }
+    }
+
+    async fn handle(&mut self, _ctx: &mut Context<Self::Address>, _message: Self::Message) {
+        let name = tracing::Span::current()
+            .metadata()
+            .map(|metadata| metadata.name());
+        self.spans.lock().unwrap().push(name);
+    }
+}
+
+#[tokio::test]
+async fn messages_are_handled_within_their_send_span() {
+    let _subscriber = tracing::subscriber::set_default(tracing_subscriber::registry());
+    let spans = Arc::new(Mutex::new(Vec::new()));
+    postmaster::register_agent!(Handler, Recorder, spans.clone()).unwrap();
+
+    postmaster::send(Addresses::Handler, Addresses::Main, Payloads::Work)
+        .await
+        .unwrap();
+    tokio::time::timeout(Duration::from_secs(1), async {
+        while spans.lock().unwrap().is_empty() {
+            tokio::task::yield_now().await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(*spans.lock().unwrap(), [Some("post_haste::send")]);
+}
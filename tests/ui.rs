@@ -0,0 +1,8 @@
+// Compile-time checks of the derives, and of the guarantees which the generated Postmaster makes through its types.
+// Regenerate the expected errors with `TRYBUILD=overwrite cargo test --test ui`.
+#[test]
+fn ui() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/pass/*.rs");
+    cases.compile_fail("tests/ui/fail/*.rs");
+}
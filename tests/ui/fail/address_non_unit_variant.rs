@@ -0,0 +1,9 @@
+use post_haste::PostHasteAddress;
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Address {
+    Display,
+    Sensor(u8),
+}
+
+fn main() {}
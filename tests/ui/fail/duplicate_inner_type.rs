@@ -0,0 +1,10 @@
+use post_haste::PostHastePayload;
+
+#[derive(PostHastePayload)]
+enum Payloads {
+    Brightness(u8),
+    Contrast(u8),
+    Volume(u8),
+}
+
+fn main() {}
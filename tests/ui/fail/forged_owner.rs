@@ -0,0 +1,33 @@
+use post_haste::{PostHasteAddress, init_postmaster};
+
+#[derive(Debug)]
+enum Payloads {
+    Ping,
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Address {
+    Main,
+    Victim,
+}
+
+init_postmaster!(Address, Payloads, sources: authenticated);
+
+// A source can't be implemented outside of the Postmaster
+struct Impostor;
+
+impl postmaster::Source for Impostor {
+    fn address(&self) -> Address {
+        Address::Victim
+    }
+}
+
+async fn take_over() {
+    // Only the owner of an address can deregister it, so no one else can register there to be handed a token
+    postmaster::deregister(Address::Victim).unwrap();
+    let _ = postmaster::send(Address::Main, Impostor, Payloads::Ping).await;
+}
+
+fn main() {
+    let _ = take_over;
+}
@@ -0,0 +1,30 @@
+use post_haste::handler::{Authority, Postmaster};
+use post_haste::mailbox::Mailbox;
+use post_haste::{PostHasteAddress, init_postmaster};
+
+#[derive(Debug)]
+enum Payloads {
+    Ping,
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Address {
+    Main,
+    Victim,
+}
+
+init_postmaster!(Address, Payloads, sources: authenticated);
+
+async fn forge() {
+    // A context's sends can't be given a bare address as their source
+    let _ = Address::send(Address::Main, Address::Victim, Payloads::Ping).await;
+    // Only post-haste can authorise an address
+    let _ = Address::authorise(Authority(core::marker::PhantomData), Address::Victim);
+    // An inbox can only be given the source of an address by the owner of its token
+    let (_mailbox, inbox) = Mailbox::<postmaster::Message>::prioritised(1, 1);
+    let _ = inbox.with_source::<Address>(Address::Victim);
+}
+
+fn main() {
+    let _ = forge;
+}
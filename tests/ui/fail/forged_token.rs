@@ -0,0 +1,21 @@
+use post_haste::{PostHasteAddress, init_postmaster};
+
+#[derive(Debug)]
+enum Payloads {
+    Ping,
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Address {
+    Main,
+    Victim,
+}
+
+init_postmaster!(Address, Payloads, sources: authenticated);
+
+fn main() {
+    // Tokens are only handed out when a mailbox is registered
+    let _ = postmaster::SourceToken {
+        address: Address::Victim,
+    };
+}
@@ -0,0 +1,60 @@
+use post_haste::agent::{Agent, Inbox};
+use post_haste::{PostHasteAddress, PostHastePayload, init_postmaster};
+
+#[derive(Debug)]
+enum DisplayMessage {
+    Clear,
+}
+
+#[derive(Debug)]
+enum SequencerMessage {
+    Begin,
+}
+
+#[derive(Debug, PostHastePayload)]
+enum Payloads {
+    Display(DisplayMessage),
+    Sequencer(SequencerMessage),
+}
+
+#[derive(Debug, Clone, Copy, PostHasteAddress)]
+enum Address {
+    Main,
+    Sequencer,
+}
+
+init_postmaster!(Address, Payloads);
+
+struct SequencerAgent;
+
+impl Agent for SequencerAgent {
+    type Address = Address;
+    type Message = postmaster::Message<SequencerMessage>;
+    type Config = ();
+
+    async fn create(_address: Self::Address, _config: Self::Config) -> Self {
+        Self
+    }
+
+    async fn run(self, mut inbox: Inbox<Self::Message>) -> ! {
+        loop {
+            let _ = inbox.recv().await;
+        }
+    }
+}
+
+async fn send(sequencer: postmaster::AgentRef<SequencerAgent>) {
+    sequencer
+        .send(Address::Main, SequencerMessage::Begin)
+        .await
+        .unwrap();
+    // The sequencer only receives SequencerMessages
+    sequencer
+        .send(Address::Main, DisplayMessage::Clear)
+        .await
+        .unwrap();
+}
+
+fn main() {
+    let _ = send;
+}
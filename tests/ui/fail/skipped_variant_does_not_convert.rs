@@ -0,0 +1,11 @@
+use post_haste::PostHastePayload;
+
+#[derive(PostHastePayload)]
+enum Payloads {
+    #[post_haste(skip)]
+    Raw(u16),
+}
+
+fn main() {
+    let _payload: Payloads = 7u16.into();
+}
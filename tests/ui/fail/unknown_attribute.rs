@@ -0,0 +1,9 @@
+use post_haste::PostHastePayload;
+
+#[derive(PostHastePayload)]
+enum Payloads {
+    #[post_haste(rename = "Level")]
+    Brightness(u8),
+}
+
+fn main() {}
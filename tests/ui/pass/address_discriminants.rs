@@ -0,0 +1,19 @@
+use post_haste::PostHasteAddress;
+
+// The discriminants are neither dense nor in order, but indices still count the variants from zero
+#[derive(Debug, Clone, Copy, PartialEq, PostHasteAddress)]
+enum Address {
+    X = 5,
+    Y = 2,
+    Z = 40,
+}
+
+fn main() {
+    assert_eq!(Address::COUNT, 3);
+    for (index, address) in [Address::X, Address::Y, Address::Z].into_iter().enumerate() {
+        assert_eq!(address.index(), index);
+        assert_eq!(Address::from_index(index), Some(address));
+    }
+    assert_eq!(Address::from_index(Address::COUNT), None);
+    assert_eq!(Address::Y.name(), "Y");
+}
@@ -0,0 +1,38 @@
+use post_haste::{PayloadVariant, PostHastePayload};
+
+#[derive(Debug, PartialEq)]
+enum DisplayMessage {
+    Clear,
+}
+
+#[derive(Debug, PartialEq, PostHastePayload)]
+enum Payloads {
+    Display(DisplayMessage),
+    Brightness(u8),
+    // Carries the same type as `Brightness`, so it is left out of the conversions
+    #[post_haste(skip)]
+    Contrast(u8),
+    // Skipped so that a bare `u16` can't be sent as a payload
+    #[post_haste(skip)]
+    Raw(u16),
+    Refresh,
+}
+
+fn main() {
+    assert_eq!(
+        Payloads::from(DisplayMessage::Clear),
+        Payloads::Display(DisplayMessage::Clear)
+    );
+    assert_eq!(Payloads::from(7u8), Payloads::Brightness(7));
+    assert!(<u8 as PayloadVariant<Payloads>>::matches(
+        &Payloads::Brightness(7)
+    ));
+    assert!(!<u8 as PayloadVariant<Payloads>>::matches(
+        &Payloads::Contrast(7)
+    ));
+    assert_eq!(
+        <u8 as PayloadVariant<Payloads>>::narrow(Payloads::Contrast(7)),
+        Err(Payloads::Contrast(7))
+    );
+    let _ = (Payloads::Raw(0), Payloads::Refresh);
+}